@@ -1,12 +1,28 @@
+use std::fmt;
+
 use serde::Serialize;
 
-use crate::ast::Identifier;
+use crate::{ast::Expression, lexer::Span};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone, Copy)]
 pub enum AggregateFunction { Count, Sum, Min, Max, Avg }
 
 #[derive(Debug, Serialize)]
 pub struct Aggregate {
     pub func: AggregateFunction,
-    pub arg: Identifier,
-}
\ No newline at end of file
+    /// The aggregated expression, absent for `count()`.
+    pub arg: Option<Box<Expression>>,
+    pub span: Span,
+}
+
+impl fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AggregateFunction::Count => write!(f, "count"),
+            AggregateFunction::Sum => write!(f, "sum"),
+            AggregateFunction::Min => write!(f, "min"),
+            AggregateFunction::Max => write!(f, "max"),
+            AggregateFunction::Avg => write!(f, "avg"),
+        }
+    }
+}
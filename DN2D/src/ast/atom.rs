@@ -1,11 +1,12 @@
 use serde::Serialize;
 
-use crate::{ast::{parser::ParseResult, Expression, Identifier, Parsable, Parser}, lexer::TokenKind};
+use crate::{ast::{parser::ParseResult, Expression, Identifier, Parsable, Parser}, lexer::{Span, TokenKind}};
 
 #[derive(Debug, Serialize)]
 pub struct Atom {
     pub name: Identifier,
     pub terms: Vec<Expression>,
+    pub span: Span,
 }
 
 impl Parsable<Atom> for Atom {
@@ -22,6 +23,6 @@ impl Parsable<Atom> for Atom {
 
         parser.expect(TokenKind::RParen)?;
 
-        Ok(Atom { name, terms })
+        Ok(Atom { span: parser.span_from(name.span), name, terms })
     }
 }
\ No newline at end of file
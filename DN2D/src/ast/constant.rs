@@ -13,16 +13,16 @@ pub enum Constant {
 impl Parsable<Constant> for Constant{
     fn parse(parser :&mut Parser<'_>) -> ParseResult<Constant> {
 
-        let token = parser.consume().ok_or_else(
-            || parser.eof_error("Expected a constant")
-        )?;
+        let token = parser.peek_or_eof("Expected a constant")?;
         
-        match token.kind {
-            TokenKind::Integer(i) => Ok(Constant::Integer(i)),
-            TokenKind::Float(f) => Ok(Constant::Float(f)),
-            TokenKind::String(s) => Ok(Constant::String(s)),
-            TokenKind::Boolean(b) => Ok(Constant::Boolean(b)),
-            _ => Err(parser.unexpected_token_error(&token, "a constant value (integer, string, etc.)")),
-        }
+        let constant = match token.kind {
+            TokenKind::Integer(i) => Constant::Integer(i),
+            TokenKind::Float(f) => Constant::Float(f),
+            TokenKind::String(s) => Constant::String(s),
+            TokenKind::Boolean(b) => Constant::Boolean(b),
+            _ => return Err(parser.unexpected_token_error(&token, "a constant value (integer, string, etc.)")),
+        };
+        parser.consume();
+        Ok(constant)
     }
 }
\ No newline at end of file
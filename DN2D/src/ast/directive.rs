@@ -1,6 +1,78 @@
 use serde::Serialize;
 
-use crate::{ast::{parser::ParseResult, Identifier, Parsable, Parser}, lexer::TokenKind};
+use crate::{ast::{parser::ParseResult, Identifier, Parsable, Parser, ParserError}, lexer::{Span, TokenKind}};
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType { Int, Float, String, Bool }
+
+#[derive(Debug, Serialize)]
+pub struct ColumnDecl {
+    pub name: Identifier,
+    pub ty: ColumnType,
+    /// Where the type is written, e.g. `int` in `age: int`.
+    pub ty_span: Span,
+    pub span: Span,
+}
+
+impl Parsable<ColumnDecl> for ColumnDecl {
+    fn parse(parser :&mut Parser<'_>) -> ParseResult<ColumnDecl> {
+
+        let name = Identifier::parse(parser)?;
+        parser.expect(TokenKind::Colon)?;
+
+        let token = parser.peek_or_eof("Expected a column type")?;
+
+        let TokenKind::Identifier(ty_name) = &token.kind else {
+            return Err(parser.unexpected_token_error(&token, "a column type"));
+        };
+        parser.consume();
+
+        let ty = match ty_name.as_str() {
+            "int" => ColumnType::Int,
+            "float" => ColumnType::Float,
+            "string" => ColumnType::String,
+            "bool" => ColumnType::Bool,
+            _ => return Err(
+                ParserError {
+                    message: format!(
+                        "Unknown column type '{}', expected 'int', 'float', 'string' or 'bool'", ty_name
+                    ),
+                    line_ref: parser.source_line(&token),
+                    span: token.span
+                }
+            )
+        };
+
+        Ok(ColumnDecl { span: name.span.to(token.span), name, ty, ty_span: token.span })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeclDirective {
+    pub name: Identifier,
+    pub columns: Vec<ColumnDecl>,
+    pub span: Span,
+}
+
+impl Parsable<DeclDirective> for DeclDirective {
+    fn parse(parser :&mut Parser<'_>) -> ParseResult<DeclDirective> {
+
+        let start = parser.expect(TokenKind::Decl)?.span;
+        let name = Identifier::parse(parser)?;
+
+        parser.expect(TokenKind::LParen)?;
+        let columns = if parser.peek_is_not(&TokenKind::RParen)? {
+            parser.parse_list(ColumnDecl::parse)?
+        } else {
+            Vec::new()
+        };
+
+        parser.expect(TokenKind::RParen)?;
+        parser.expect(TokenKind::Dot)?;
+
+        Ok(DeclDirective { name, columns, span: parser.span_from(start) })
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub struct ReadDirective {
@@ -8,12 +80,14 @@ pub struct ReadDirective {
     pub columns: Vec<Identifier>,
     pub path: String,
     pub format: String,
+    pub format_span: Span,
+    pub span: Span,
 }
 
 impl Parsable<ReadDirective> for  ReadDirective {
     fn parse(parser :&mut Parser<'_>) -> ParseResult<ReadDirective> {
 
-        parser.expect(TokenKind::Read)?;
+        let start = parser.expect(TokenKind::Read)?.span;
         let name = Identifier::parse(parser)?;
 
         parser.expect(TokenKind::LParen)?;
@@ -23,12 +97,13 @@ impl Parsable<ReadDirective> for  ReadDirective {
         parser.expect(TokenKind::From)?;
         let path = parser.parse_string_literal()?;
 
-        parser.expect(TokenKind::As)?;
+        let as_token = parser.expect(TokenKind::As)?;
+        let format_span = parser.peek().map_or(as_token.span, |token| token.span);
         let format = parser.parse_string_literal()?;
 
         parser.expect(TokenKind::Dot)?;
         
-        Ok(ReadDirective { name, columns, path, format })   
+        Ok(ReadDirective { name, columns, path, format, format_span, span: parser.span_from(start) })   
     }
 }
 
@@ -37,22 +112,25 @@ pub struct WriteDirective {
     pub name: Identifier,
     pub path: String,
     pub format: String,
+    pub format_span: Span,
+    pub span: Span,
 }
 
 impl Parsable<WriteDirective> for  WriteDirective {
     fn parse(parser :&mut Parser<'_>) -> ParseResult<WriteDirective> {
         
-        parser.expect(TokenKind::Write)?;
+        let start = parser.expect(TokenKind::Write)?.span;
         let name = Identifier::parse(parser)?;
 
         parser.expect(TokenKind::To)?;
         let path = parser.parse_string_literal()?;
 
-        parser.expect(TokenKind::As)?;
+        let as_token = parser.expect(TokenKind::As)?;
+        let format_span = parser.peek().map_or(as_token.span, |token| token.span);
         let format = parser.parse_string_literal()?;
         
         parser.expect(TokenKind::Dot)?;
 
-        Ok(WriteDirective { name, path, format })
+        Ok(WriteDirective { name, path, format, format_span, span: parser.span_from(start) })
     }
 }
\ No newline at end of file
@@ -1,12 +1,12 @@
 use serde::Serialize;
 
-use crate::{ast::{identifier::Identifier, parser::ParseResult, Aggregate, AggregateFunction, BinaryOperator, Constant, Parsable, Parser, ParserError, UnaryOperator}, lexer::TokenKind};
+use crate::{ast::{identifier::Identifier, parser::ParseResult, Aggregate, AggregateFunction, BinaryOperator, Constant, Parsable, Parser, ParserError, UnaryOperator}, lexer::{Span, TokenKind}};
 
 #[derive(Debug, Serialize)]
 pub enum Expression {
-    Constant(Constant),
+    Constant(Constant, Span),
     Variable(Identifier),
-    Wildcard,
+    Wildcard(Span),
     Aggregate(Aggregate),
     Binary {
         left: Box<Expression>,
@@ -16,8 +16,9 @@ pub enum Expression {
     Unary {
         op: UnaryOperator,
         expr: Box<Expression>,
+        span: Span,
     },
-    Paren(Box<Expression>),
+    Paren(Box<Expression>, Span),
 }
 
 impl Parsable<Expression> for Expression {
@@ -27,6 +28,42 @@ impl Parsable<Expression> for Expression {
 }
 
 impl Expression{
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Constant(_, span)
+            | Expression::Wildcard(span)
+            | Expression::Unary { span, .. }
+            | Expression::Paren(_, span) => *span,
+            Expression::Variable(name) => name.span,
+            Expression::Aggregate(aggregate) => aggregate.span,
+            Expression::Binary { left, right, .. } => left.span().to(right.span()),
+        }
+    }
+
+    /// Returns the variables referenced by the expression, in order of appearance.
+    pub fn variables(&self) -> Vec<&Identifier> {
+        let mut variables = Vec::new();
+        self.collect_variables(&mut variables);
+        variables
+    }
+
+    fn collect_variables<'a>(&'a self, variables: &mut Vec<&'a Identifier>) {
+        match self {
+            Expression::Variable(name) => variables.push(name),
+            Expression::Aggregate(aggregate) => {
+                if let Some(arg) = &aggregate.arg {
+                    arg.collect_variables(variables);
+                }
+            }
+            Expression::Binary { left, right, .. } => {
+                left.collect_variables(variables);
+                right.collect_variables(variables);
+            }
+            Expression::Unary { expr, .. } | Expression::Paren(expr, _) => expr.collect_variables(variables),
+            Expression::Constant(..) | Expression::Wildcard(_) => {}
+        }
+    }
+
     fn parse_comparison(parser :&mut Parser<'_>) -> ParseResult<Expression> {
 
         let mut expr = Expression::parse_additive(parser)?;
@@ -90,10 +127,11 @@ impl Expression{
 
         if parser.peek_is(&TokenKind::Minus)? {
 
-            parser.consume();
+            let start = parser.expect(TokenKind::Minus)?.span;
 
             let expr = Expression::parse_unary(parser)?;
-            Ok(Expression::Unary { op: UnaryOperator::Neg, expr: Box::new(expr) })
+            let span = start.to(expr.span());
+            Ok(Expression::Unary { op: UnaryOperator::Neg, expr: Box::new(expr), span })
         } else {
             Expression::parse_primary(parser)
         }
@@ -101,24 +139,26 @@ impl Expression{
     
     fn parse_primary(parser :&mut Parser<'_>) -> ParseResult<Expression> {
 
-        let token = parser.consume().ok_or_else(
-            || parser.eof_error("Expected a primary expression")
-        )?;
-        
+        let token = parser.peek_or_eof("Expected a primary expression")?;
+
+        if !matches!(
+            token.kind,
+            TokenKind::Integer(_) | TokenKind::Float(_) | TokenKind::String(_) | TokenKind::Boolean(_)
+                | TokenKind::Identifier(_) | TokenKind::Wildcard | TokenKind::LParen
+        ) {
+            return Err(parser.unexpected_token_error(&token, "a literal, identifier, or expression"));
+        }
+        parser.consume();
+
         match &token.kind {
-            TokenKind::Integer(i) => Ok(Expression::Constant(Constant::Integer(*i))),
-            TokenKind::Float(f) => Ok(Expression::Constant(Constant::Float(*f))),
-            TokenKind::String(s) => Ok(Expression::Constant(Constant::String(s.clone()))),
-            TokenKind::Boolean(b) => Ok(Expression::Constant(Constant::Boolean(*b))),
+            TokenKind::Integer(i) => Ok(Expression::Constant(Constant::Integer(*i), token.span)),
+            TokenKind::Float(f) => Ok(Expression::Constant(Constant::Float(*f), token.span)),
+            TokenKind::String(s) => Ok(Expression::Constant(Constant::String(s.clone()), token.span)),
+            TokenKind::Boolean(b) => Ok(Expression::Constant(Constant::Boolean(*b), token.span)),
             TokenKind::Identifier(name) => {
                 
                 if parser.peek_is(&TokenKind::LParen)? {
 
-                    parser.consume();
-                    let arg = Identifier::parse(parser)?;
-
-                    parser.expect(TokenKind::RParen)?;
-
                     let func = match name.as_str() {
                         "count" => AggregateFunction::Count,
                         "sum" => AggregateFunction::Sum,
@@ -133,18 +173,37 @@ impl Expression{
                             }
                         )
                     };
-                    Ok(Expression::Aggregate(Aggregate { func, arg }))
+
+                    parser.consume();
+                    let arg = if parser.peek_is(&TokenKind::RParen)? {
+                        None
+                    } else {
+                        Some(Box::new(Expression::parse(parser)?))
+                    };
+
+                    parser.expect(TokenKind::RParen)?;
+
+                    if arg.is_none() && !matches!(func, AggregateFunction::Count) {
+                        return Err(
+                            ParserError {
+                                message: format!("Aggregate function '{}' requires an argument", name),
+                                line_ref: parser.source_line(&token),
+                                span: token.span
+                            }
+                        );
+                    }
+                    Ok(Expression::Aggregate(Aggregate { func, arg, span: parser.span_from(token.span) }))
                 } else {
-                    Ok(Expression::Variable(Identifier(name.clone())))
+                    Ok(Expression::Variable(Identifier::new(name.clone(), token.span)))
                 }
             }
-            TokenKind::Wildcard => Ok(Expression::Wildcard),
+            TokenKind::Wildcard => Ok(Expression::Wildcard(token.span)),
             TokenKind::LParen => {
                 let expr = Expression::parse(parser)?;
                 parser.expect(TokenKind::RParen)?;
-                Ok(Expression::Paren(Box::new(expr)))
+                Ok(Expression::Paren(Box::new(expr), parser.span_from(token.span)))
             },
-            _ => Err(parser.unexpected_token_error(&token, "a literal, identifier, or expression")),
+            _ => unreachable!("checked to begin a primary expression above"),
         }
     }
 }
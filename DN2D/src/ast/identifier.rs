@@ -1,22 +1,68 @@
+use std::{cmp::Ordering, hash::{Hash, Hasher}};
+
 use serde::Serialize;
 
-use crate::{ast::{parser::ParseResult, Parsable, Parser}, lexer::TokenKind};
+use crate::{ast::{parser::ParseResult, Parsable, Parser}, lexer::{Span, TokenKind}};
+
+/// A relation or variable name, together with where it appears in the source.
+///
+/// Identifiers compare and hash by name only, so the same relation or variable
+/// is found in lookups regardless of where it is mentioned.
+#[derive(Debug, Serialize, Clone)]
+pub struct Identifier {
+    pub name: String,
+    pub span: Span,
+}
+
+impl Identifier {
+    pub fn new(name: impl Into<String>, span: Span) -> Self {
+        Identifier { name: name.into(), span }
+    }
+}
+
+impl PartialEq for Identifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Identifier {}
 
-#[derive(Debug, Serialize, Clone, PartialEq, Eq, Hash)]
-pub struct Identifier(pub String);
+impl PartialOrd for Identifier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Identifier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name.cmp(&other.name)
+    }
+}
+
+impl Hash for Identifier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl std::fmt::Display for Identifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
 
 
 impl Parsable<Identifier> for Identifier {
     fn parse(parser :&mut Parser<'_>) -> ParseResult<Identifier> {
 
-        let token = parser.consume().ok_or_else(
-            || parser.eof_error("Expected an identifier")
-        )?;
+        let token = parser.peek_or_eof("Expected an identifier")?;
 
         if let TokenKind::Identifier(name) = token.kind {
-            Ok(Identifier(name))
+            parser.consume();
+            Ok(Identifier::new(name, token.span))
         } else {
             Err(parser.unexpected_token_error(&token, " an identifier"))
         }
     }
-}
\ No newline at end of file
+}
@@ -1,24 +1,29 @@
 use serde::Serialize;
 
-use crate::{ast::{parser::ParseResult, Parsable, Parser, RuleOrFact}, lexer::TokenKind};
+use crate::{ast::{parser::ParseResult, Parsable, Parser, RuleOrFact}, lexer::{Span, TokenKind}};
 
 #[derive(Debug, Serialize)]
 pub struct IterationBlock {
     pub rules: Vec<RuleOrFact>,
+    pub span: Span,
 }
 
 impl Parsable<IterationBlock> for IterationBlock {
     fn parse(parser :&mut Parser<'_>) -> ParseResult<IterationBlock> {
 
-        parser.expect(TokenKind::Iterate)?;
+        let start = parser.expect(TokenKind::Iterate)?.span;
         parser.expect(TokenKind::LBrace)?;
         let mut rules = Vec::new();
 
         while parser.peek_is_not(&TokenKind::RBrace)? {
-            rules.push(RuleOrFact::parse(parser)?);
+            let start = parser.position();
+            match RuleOrFact::parse(parser) {
+                Ok(rule_or_fact) => rules.push(rule_or_fact),
+                Err(e) => parser.recover(e, start, None),
+            }
         }
         parser.expect(TokenKind::RBrace)?;
         
-        Ok(IterationBlock { rules })
+        Ok(IterationBlock { rules, span: parser.span_from(start) })
     }
 }
\ No newline at end of file
@@ -3,6 +3,7 @@ pub mod rule_or_fact;
 pub mod parser;
 pub mod literal;
 pub mod program;
+pub mod query;
 pub mod operator;
 pub mod constant;
 pub mod statement;
@@ -17,8 +18,9 @@ pub mod parsable_trait;
 pub use atom::Atom;
 pub use parser::Parser;
 pub use literal::Literal;
-pub use rule_or_fact::RuleOrFact;
+pub use rule_or_fact::{Fact, Rule, RuleOrFact};
 pub use program::Program;
+pub use query::Query;
 pub use constant::Constant;
 pub use statement::Statement;
 pub use expression::Expression;
@@ -28,4 +30,4 @@ pub use parser_error::ParserError;
 pub use iteration::IterationBlock;
 pub use operator::{BinaryOperator, UnaryOperator};
 pub use aggregate::{Aggregate, AggregateFunction};
-pub use directive::{ReadDirective, WriteDirective};
\ No newline at end of file
+pub use directive::{ColumnType, DeclDirective, ReadDirective, WriteDirective};
\ No newline at end of file
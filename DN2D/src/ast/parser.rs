@@ -1,30 +1,39 @@
 use std::{iter::Peekable, vec};
 
-use crate::{ast::ParserError, lexer::{Span, Token, TokenKind}};
+use crate::{ast::ParserError, diagnostics::source_line, lexer::{Span, Token, TokenKind}};
 
 pub type ParseResult<T> = Result<T, ParserError>;
 
 pub struct Parser<'a> {
     pub(crate) tokens: Peekable<vec::IntoIter<Token>>,
     pub source: &'a str,
+    /// The errors recovered from so far, in the order they were found.
+    pub errors: Vec<ParserError>,
+    /// The number of tokens consumed so far.
+    position: usize,
+    /// The spans of the `Illegal` tokens, which the lexer already reported.
+    illegal: Vec<Span>,
+    previous: Option<Token>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(source: &'a str, tokens: Vec<Token>) -> Self {
+        let illegal = tokens.iter()
+            .filter(|token| token.kind == TokenKind::Illegal)
+            .map(|token| token.span)
+            .collect();
         Parser {
             tokens: tokens.into_iter().peekable(),
             source,
+            errors: Vec::new(),
+            position: 0,
+            illegal,
+            previous: None,
         }
     }
 
     pub fn source_line(&self, token: &Token) -> String {
-        match self.source.lines().nth(token.span.line -1) {
-            Some(line) => line.to_string(),
-            None => panic!(
-                "Parser Error: Cannot retrieve the line of the token in {}:{}:{}",
-                  token.span.line, token.span.start, token.span.end
-            )
-        }
+        source_line(self.source, token.span.start_line)
     }
 
     pub fn parse_list<T, F>(&mut self, mut parse_fn: F) -> ParseResult<Vec<T>>
@@ -42,8 +51,9 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse_string_literal(&mut self) -> ParseResult<String> {
-        let token = self.consume().ok_or_else(|| self.eof_error("Expected a string literal"))?;
+        let token = self.peek_or_eof("Expected a string literal")?;
         if let TokenKind::String(s) = token.kind {
+            self.consume();
             Ok(s)
         } else {
             Err(self.unexpected_token_error(&token, "a string literal"))
@@ -55,35 +65,113 @@ impl<'a> Parser<'a> {
     }
 
     pub fn consume(&mut self) -> Option<Token> {
-        self.tokens.next()
+        let token = self.tokens.next()?;
+        self.position += 1;
+        self.previous = Some(token.clone());
+        Some(token)
+    }
+
+    /// The number of tokens consumed so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The span from `start` to the end of the last token consumed.
+    pub fn span_from(&self, start: Span) -> Span {
+        self.previous.as_ref().map_or(start, |token| start.to(token.span))
+    }
+
+    /// Records `error` and skips the rest of the statement it was found in, so
+    /// that parsing can resume with the next statement. `start` is the
+    /// position at which that statement began and `directive_line` the line it
+    /// began on if it is a directive.
+    pub fn recover(&mut self, error: ParserError, start: usize, directive_line: Option<usize>) {
+        if !self.illegal.contains(&error.span) {
+            self.errors.push(error);
+        }
+        if self.position == start {
+            self.consume();
+        }
+        self.synchronize(directive_line);
+    }
+
+    /// Skips tokens up to and including the next `.`, or up to the next `}`,
+    /// directive or query, unless the last token consumed already ended a
+    /// statement. As directives fit on one line, recovery from one also stops
+    /// at the first token of a later line, in case its `.` is missing.
+    fn synchronize(&mut self, directive_line: Option<usize>) {
+        if self.previous.as_ref().is_some_and(|token| token.kind == TokenKind::Dot) {
+            return;
+        }
+        while let Some(token) = self.peek() {
+            if directive_line.is_some_and(|line| token.span.start_line > line) {
+                return;
+            }
+            match token.kind {
+                TokenKind::Dot => {
+                    self.consume();
+                    return;
+                }
+                TokenKind::RBrace
+                | TokenKind::Decl
+                | TokenKind::Read
+                | TokenKind::Write
+                | TokenKind::Iterate
+                | TokenKind::QueryDash => return,
+                _ => {
+                    self.consume();
+                }
+            }
+        }
     }
     
+    /// Consumes the next token if it is of the `expected` kind. Any other token
+    /// is left in place, so that recovery can resume at it when it begins the
+    /// next statement.
     pub fn expect(&mut self, expected: TokenKind) -> ParseResult<Token> {
-        let token = self.consume().ok_or_else(|| self.eof_error(&format!("Expected '{:?}'", expected)))?;
+        let token = self.peek_or_eof(&format!("Expected '{:?}'", expected))?;
         if std::mem::discriminant(&token.kind) == std::mem::discriminant(&expected) {
+            self.consume();
             Ok(token)
         } else {
             Err(self.unexpected_token_error(&token, &format!("'{:?}'", expected)))
         }
     }
 
+    /// A copy of the next token, which is left in place, or an error with
+    /// `message` at the end of the source.
+    pub fn peek_or_eof(&mut self, message: &str) -> ParseResult<Token> {
+        match self.peek() {
+            Some(token) => Ok(token.clone()),
+            None => Err(self.eof_error(message)),
+        }
+    }
+
     pub fn peek_is(&mut self, kind: &TokenKind) -> ParseResult<bool> {
-        Ok(self.peek().map_or(false, |t| std::mem::discriminant(&t.kind) == std::mem::discriminant(kind)))
+        Ok(self.peek().is_some_and(|t| std::mem::discriminant(&t.kind) == std::mem::discriminant(kind)))
     }
 
     pub fn peek_is_not(&mut self, kind: &TokenKind) -> ParseResult<bool> {
-        Ok(self.peek().map_or(false, |t| std::mem::discriminant(&t.kind) != std::mem::discriminant(kind)))
+        Ok(self.peek().is_some_and(|t| std::mem::discriminant(&t.kind) != std::mem::discriminant(kind)))
     }
         
+    /// An error at the end of the source, just after its last token.
     pub fn eof_error(&self, message: &str) -> ParserError {
+        let (offset, line, column) = match &self.previous {
+            Some(token) => (token.span.end_offset, token.span.end_line, token.span.end_column + 1),
+            None => (0, 1, 1),
+        };
         ParserError {
-            message: message.to_string(),
-            line_ref: "".to_string(),
+            message: format!("{}, found the end of the file", message),
+            line_ref: source_line(self.source, line),
             span: Span {
-                line: 0,
-                start: 0,
-                end: 0
-            }
+                start_offset: offset,
+                end_offset: offset,
+                start_line: line,
+                start_column: column,
+                end_line: line,
+                end_column: column,
+            },
         }
     }
 
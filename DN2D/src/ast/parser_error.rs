@@ -1,4 +1,4 @@
-use crate::lexer::Span;
+use crate::{diagnostics::{Code, Diagnostic, Label}, lexer::Span};
 
 
 #[derive(Debug)]
@@ -8,34 +8,22 @@ pub struct ParserError {
     pub span: Span,
 }
 
+impl ParserError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(Code::SyntaxError, self.message.clone());
+        diagnostic.labels.push(Label {
+            span: self.span,
+            message: String::new(),
+            line_ref: self.line_ref.clone(),
+            primary: true,
+        });
+        diagnostic
+    }
+}
+
 impl std::fmt::Display for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-
-        let mut txt_pointer = String::new();
-        if self.line_ref != "" { 
-            txt_pointer.push_str("\n\n");
-            txt_pointer.push_str(&self.line_ref);
-            txt_pointer.push_str("\n");
-            txt_pointer.push_str(&" ".repeat(self.span.start -1));
-            
-            if self.span.start >= 2 && self.span.start != self.span.end {
-                txt_pointer.push_str(&"┗");
-                txt_pointer.push_str(&"━".repeat(self.span.start -2));
-                txt_pointer.push_str(&"┛");
-            } else {
-                txt_pointer.push_str(&"┻");
-            }
-        }
-
-        write!(
-            f,
-            "Parser Error at Line {}, Col {}-{}: {}.{}",
-            self.span.line,
-            self.span.start,
-            self.span.end,
-            self.message,
-            txt_pointer
-        )
+        write!(f, "{}", self.diagnostic())
     }
 }
-impl std::error::Error for ParserError {}
\ No newline at end of file
+impl std::error::Error for ParserError {}
@@ -1,20 +1,125 @@
 use serde::Serialize;
 
-use crate::ast::{parser::ParseResult, Parsable, Parser, Statement};
+use crate::{
+    ast::{parser::ParseResult, Fact, Parsable, Parser, ParserError, Query, Rule, RuleOrFact, Statement},
+    lexer::TokenKind,
+};
 
 #[derive(Debug, Serialize)]
 pub struct Program {
     pub statements: Vec<Statement>,
 }
 
-impl Parsable<Program> for Program{
-    fn parse(parser :&mut Parser<'_>) -> ParseResult<Program> {
+impl Program {
+    /// Every rule of the program, including those inside `.iterate` blocks.
+    pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+        self.statements.iter().flat_map(|statement| match statement {
+            Statement::Rule(rule) => vec![rule],
+            Statement::Iterate(block) => block.rules.iter()
+                .filter_map(|rule_or_fact| match rule_or_fact {
+                    RuleOrFact::Rule(rule) => Some(rule),
+                    RuleOrFact::Fact(_) => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    /// Every fact of the program, including those inside `.iterate` blocks.
+    pub fn facts(&self) -> impl Iterator<Item = &Fact> {
+        self.statements.iter().flat_map(|statement| match statement {
+            Statement::Fact(fact) => vec![fact],
+            Statement::Iterate(block) => block.rules.iter()
+                .filter_map(|rule_or_fact| match rule_or_fact {
+                    RuleOrFact::Fact(fact) => Some(fact),
+                    RuleOrFact::Rule(_) => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    pub fn queries(&self) -> impl Iterator<Item = &Query> {
+        self.statements.iter().filter_map(|statement| match statement {
+            Statement::Query(query) => Some(query),
+            _ => None,
+        })
+    }
+
+    /// Parses every statement of a program, recovering from each syntax error
+    /// at the start of the next statement. Returns the statements that could
+    /// be parsed along with every error found.
+    pub fn parse_all(parser: &mut Parser<'_>) -> (Program, Vec<ParserError>) {
         let mut statements = Vec::new();
 
         while parser.peek().is_some() {
-            statements.push(Statement::parse(parser)?);
+            let start = parser.position();
+            let directive_line = parser.peek()
+                .filter(|token| matches!(token.kind, TokenKind::Decl | TokenKind::Read | TokenKind::Write))
+                .map(|token| token.span.start_line);
+            match Statement::parse(parser) {
+                Ok(statement) => statements.push(statement),
+                Err(e) => parser.recover(e, start, directive_line),
+            }
         }
 
-        Ok(Program { statements })
+        (Program { statements }, std::mem::take(&mut parser.errors))
     }
-}
\ No newline at end of file
+}
+
+impl Parsable<Program> for Program{
+    fn parse(parser :&mut Parser<'_>) -> ParseResult<Program> {
+        let (program, errors) = Program::parse_all(parser);
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(program),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Statement, lexer::Lexer};
+
+    fn parse(source: &str) -> (Program, Vec<ParserError>) {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty());
+        Program::parse_all(&mut Parser::new(source, tokens))
+    }
+
+    fn heads(rules: &[RuleOrFact]) -> Vec<&str> {
+        rules.iter()
+            .map(|rule_or_fact| match rule_or_fact {
+                RuleOrFact::Rule(rule) => rule.head.name.name.as_str(),
+                RuleOrFact::Fact(fact) => fact.head.name.name.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recovers_from_every_error_including_inside_iterate() {
+        let (program, errors) = parse(
+            ".decl E(a: int, b: number)\nE(1, 2).\n.iterate {\n  R(x) :- E(x, _).\n  R(x) :- E(x,\n}\nS(x) :- E(x, 2) 3.\nT(x) :- E(x, _).\n"
+        );
+
+        let errors: Vec<(usize, usize, &str)> = errors.iter()
+            .map(|error| (error.span.start_line, error.span.start_column, error.message.as_str()))
+            .collect();
+        assert_eq!(errors, vec![
+            (1, 20, "Unknown column type 'number', expected 'int', 'float', 'string' or 'bool'"),
+            (6, 1, "Unexpected token 'RBrace', expected a literal, identifier, or expression"),
+            (7, 17, "Unexpected token 'Integer(3)', expected 'Dot'"),
+        ]);
+
+        let statements: Vec<String> = program.statements.iter()
+            .map(|statement| match statement {
+                Statement::Fact(fact) => format!("fact {}", fact.head.name),
+                Statement::Rule(rule) => format!("rule {}", rule.head.name),
+                Statement::Iterate(block) => format!("iterate {:?}", heads(&block.rules)),
+                other => format!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(statements, vec!["fact E", "iterate [\"R\"]", "rule T"]);
+    }
+}
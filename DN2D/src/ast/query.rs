@@ -0,0 +1,69 @@
+use serde::Serialize;
+
+use crate::{ast::{parser::ParseResult, Expression, Identifier, Literal, Parsable, Parser}, lexer::{Span, TokenKind}};
+
+/// A goal whose answers are printed once the program has been evaluated,
+/// e.g. `?- Path(1, x), x > 3.`
+#[derive(Debug, Serialize)]
+pub struct Query {
+    pub body: Vec<Literal>,
+    pub span: Span,
+}
+
+impl Query {
+    /// The variables bound by the positive atoms of the goal, in order of
+    /// first appearance; each answer gives a value for each of them.
+    pub fn variables(&self) -> Vec<&Identifier> {
+        let mut variables: Vec<&Identifier> = Vec::new();
+        for literal in &self.body {
+            if let Literal::Positive(atom) = literal {
+                for term in &atom.terms {
+                    if let Expression::Variable(name) = term {
+                        if !variables.contains(&name) {
+                            variables.push(name);
+                        }
+                    }
+                }
+            }
+        }
+        variables
+    }
+}
+
+impl Parsable<Query> for Query {
+    fn parse(parser :&mut Parser<'_>) -> ParseResult<Query> {
+
+        let start = parser.expect(TokenKind::QueryDash)?.span;
+        let body = parser.parse_list(Literal::parse)?;
+        parser.expect(TokenKind::Dot)?;
+
+        Ok(Query { body, span: parser.span_from(start) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn parse(source: &str) -> Query {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty());
+        Query::parse(&mut Parser::new(source, tokens)).unwrap()
+    }
+
+    #[test]
+    fn variables_are_those_of_positive_atoms_in_order_of_appearance() {
+        let query = parse("?- Edge(y, x), Edge(x, _), not Seen(z), y > w, Path(y, v).");
+        let variables: Vec<&str> = query.variables().iter().map(|variable| variable.name.as_str()).collect();
+        assert_eq!(variables, vec!["y", "x", "v"]);
+    }
+
+    #[test]
+    fn a_query_spans_from_its_dash_to_its_dot() {
+        let query = parse("\n?- Edge(1, x),\n   x > 2.");
+        assert_eq!(query.body.len(), 2);
+        assert_eq!((query.span.start_line, query.span.start_column), (2, 1));
+        assert_eq!((query.span.end_line, query.span.end_column), (3, 9));
+    }
+}
@@ -1,6 +1,6 @@
 use serde::Serialize;
 
-use crate::{ast::{parser::ParseResult, Atom, Literal, Parsable, Parser}, lexer::TokenKind};
+use crate::{ast::{parser::ParseResult, Atom, Literal, Parsable, Parser}, lexer::{Span, TokenKind}};
 
 #[derive(Debug, Serialize)]
 pub enum RuleOrFact {
@@ -12,18 +12,20 @@ pub enum RuleOrFact {
 pub struct Rule {
     pub head: Atom,
     pub body: Vec<Literal>,
+    pub span: Span,
 }
 
 #[derive(Debug, Serialize)]
 pub struct Fact {
     pub head: Atom,
+    pub span: Span,
 }
 
 impl Parsable<RuleOrFact> for RuleOrFact{
     fn parse(parser :&mut Parser<'_>) -> ParseResult<RuleOrFact> {
         
         let head = Atom::parse(parser)?;
-        let token = parser.consume().unwrap();
+        let token = parser.peek_or_eof("Expected ':-' or '.'")?;
         
         let is_colon_dash = token.kind == TokenKind::ColonDash;
         let is_dot = token.kind == TokenKind::Dot;
@@ -34,6 +36,7 @@ impl Parsable<RuleOrFact> for RuleOrFact{
                     &format!("'{:?} or {:?}'", TokenKind::ColonDash, TokenKind::Dot)
             ));
         }
+        parser.consume();
         
         let mut body = Vec::new();
         if is_colon_dash {
@@ -45,7 +48,7 @@ impl Parsable<RuleOrFact> for RuleOrFact{
             }
 
             parser.expect(TokenKind::Dot)?;
-            return Ok(RuleOrFact::Rule(Rule { head, body }));
+            return Ok(RuleOrFact::Rule(Rule { span: parser.span_from(head.span), head, body }));
         }
 
         if !is_dot {
@@ -57,6 +60,6 @@ impl Parsable<RuleOrFact> for RuleOrFact{
             );
         }
 
-        Ok(RuleOrFact::Fact(Fact { head }))
+        Ok(RuleOrFact::Fact(Fact { span: parser.span_from(head.span), head }))
     }
 }
\ No newline at end of file
@@ -1,15 +1,17 @@
 
 use serde::Serialize;
 
-use crate::{ast::{parser::ParseResult, rule_or_fact::{Fact, Rule}, IterationBlock, Parsable, Parser, ReadDirective, RuleOrFact, WriteDirective}, lexer::TokenKind};
+use crate::{ast::{parser::ParseResult, rule_or_fact::{Fact, Rule}, DeclDirective, IterationBlock, Parsable, Parser, Query, ReadDirective, RuleOrFact, WriteDirective}, lexer::TokenKind};
 
 #[derive(Debug, Serialize)]
 pub enum Statement {
+    Decl(DeclDirective),
     Read(ReadDirective),
     Write(WriteDirective),
     Iterate(IterationBlock),
     Rule(Rule),
     Fact(Fact),
+    Query(Query),
 }
 
 impl Parsable<Statement> for Statement{
@@ -20,14 +22,18 @@ impl Parsable<Statement> for Statement{
             .ok_or_else(|| parser.eof_error("Expected a statement"))?;
         
         match &token.kind {
+            TokenKind::Decl => DeclDirective::parse(parser)
+                .map(Statement::Decl),
             TokenKind::Read => ReadDirective::parse(parser)
                 .map(Statement::Read),
             TokenKind::Write => WriteDirective::parse(parser)
                 .map(Statement::Write),
             TokenKind::Iterate => IterationBlock::parse(parser)
                 .map(Statement::Iterate),
+            TokenKind::QueryDash => Query::parse(parser)
+                .map(Statement::Query),
             TokenKind::Identifier(_) => {
-                return match RuleOrFact::parse(parser) {
+                match RuleOrFact::parse(parser) {
                     Ok(rule_or_fact) => {
                         match  rule_or_fact{
                             RuleOrFact::Rule(rule) => Ok(Statement::Rule(rule)),
@@ -35,7 +41,7 @@ impl Parsable<Statement> for Statement{
                         }
                     },
                     Err(e) => Err(e)
-                };
+                }
             }
             _ => Err(
                 parser.unexpected_token_error(
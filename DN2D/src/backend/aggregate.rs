@@ -0,0 +1,113 @@
+use std::cmp::Ordering;
+
+use crate::{
+    ast::{AggregateFunction, BinaryOperator},
+    backend::{expr::{binary, compare}, value::Float, ScalarExpr, Value},
+};
+
+/// An aggregate head term, computed over the bindings of a rule body that
+/// agree on every non-aggregate head term.
+#[derive(Debug, Clone)]
+pub struct AggregatePlan {
+    pub func: AggregateFunction,
+    pub arg: ScalarExpr,
+}
+
+impl AggregatePlan {
+    /// Folds the argument values of one group, each occurring `diff` times.
+    ///
+    /// Returns `None` when the group has no value, e.g. when summing strings.
+    pub fn apply<'a>(&self, values: impl Iterator<Item = (&'a Value, isize)>) -> Option<Value> {
+        match self.func {
+            AggregateFunction::Count => {
+                Some(Value::Integer(values.map(|(_, diff)| diff as i64).sum()))
+            }
+            AggregateFunction::Sum => sum(values).map(|(sum, _)| sum),
+            AggregateFunction::Avg => {
+                let (sum, count) = sum(values)?;
+                Some(Value::Float(Float(sum.as_f64()? / count as f64)))
+            }
+            AggregateFunction::Min => extremum(values, Ordering::Less),
+            AggregateFunction::Max => extremum(values, Ordering::Greater),
+        }
+    }
+}
+
+/// The sum of the values and how many there are.
+fn sum<'a>(values: impl Iterator<Item = (&'a Value, isize)>) -> Option<(Value, i64)> {
+    let mut sum = Value::Integer(0);
+    let mut count = 0;
+    for (value, diff) in values {
+        let total = binary(BinaryOperator::Mul, value.clone(), Value::Integer(diff as i64))?;
+        sum = binary(BinaryOperator::Add, sum, total)?;
+        count += diff as i64;
+    }
+    Some((sum, count))
+}
+
+/// The value that compares as `wanted` against all others.
+fn extremum<'a>(mut values: impl Iterator<Item = (&'a Value, isize)>, wanted: Ordering) -> Option<Value> {
+    let (mut best, _) = values.next()?;
+    for (value, _) in values {
+        if compare(value, best)? == wanted {
+            best = value;
+        }
+    }
+    Some(best.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::{
+        engine::tests::{compile, relation, rows, tuples},
+        Semantics, Value,
+    };
+
+    fn sale(shop: &str, amount: i64) -> Vec<Value> {
+        vec![Value::String(shop.to_string()), Value::Integer(amount)]
+    }
+
+    #[test]
+    fn aggregates_follow_insertions_and_retractions() {
+        let mut engine = compile(
+            ".decl Sale(shop: string, amount: int).\nSale(\"a\", 1).\nSale(\"a\", 5).\nSale(\"a\", 3).\nSale(\"b\", 2).\n\
+             Stats(shop, count(), sum(amount), avg(amount), min(amount), max(amount)) :- Sale(shop, amount).\n",
+            Semantics::Stratified,
+        );
+        let stats = relation("Stats");
+        let sales = relation("Sale");
+        engine.track_changes(&stats).unwrap();
+        assert_eq!(tuples(&engine, "Stats"), vec!["\"a\", 3, 9, 3.0, 1, 5", "\"b\", 1, 2, 2.0, 2, 2"]);
+
+        engine.insert(&sales, sale("a", 7)).unwrap();
+        engine.insert(&sales, sale("b", 4)).unwrap();
+        engine.commit();
+        assert_eq!(tuples(&engine, "Stats"), vec!["\"a\", 4, 16, 4.0, 1, 7", "\"b\", 2, 6, 3.0, 2, 4"]);
+
+        // Retracts the minimum of one group and the maximum of the other.
+        engine.update(&sales, sale("a", 1), -1).unwrap();
+        engine.update(&sales, sale("b", 4), -1).unwrap();
+        engine.commit();
+        assert_eq!(tuples(&engine, "Stats"), vec!["\"a\", 3, 15, 5.0, 3, 7", "\"b\", 1, 2, 2.0, 2, 2"]);
+
+        engine.update(&sales, sale("b", 2), -1).unwrap();
+        engine.commit();
+        assert_eq!(tuples(&engine, "Stats"), vec!["\"a\", 3, 15, 5.0, 3, 7"]);
+
+        let changes: Vec<(u64, isize, Vec<String>)> = engine.take_changes(&stats).unwrap().into_iter()
+            .map(|change| (change.epoch, change.diff, rows(vec![change.tuple])))
+            .collect();
+        let change = |epoch, diff, row: &str| (epoch, diff, vec![row.to_string()]);
+        assert_eq!(changes, vec![
+            change(1, -1, "\"a\", 3, 9, 3.0, 1, 5"),
+            change(1, 1, "\"a\", 4, 16, 4.0, 1, 7"),
+            change(1, -1, "\"b\", 1, 2, 2.0, 2, 2"),
+            change(1, 1, "\"b\", 2, 6, 3.0, 2, 4"),
+            change(2, 1, "\"a\", 3, 15, 5.0, 3, 7"),
+            change(2, -1, "\"a\", 4, 16, 4.0, 1, 7"),
+            change(2, 1, "\"b\", 1, 2, 2.0, 2, 2"),
+            change(2, -1, "\"b\", 2, 6, 3.0, 2, 4"),
+            change(3, -1, "\"b\", 1, 2, 2.0, 2, 2"),
+        ]);
+    }
+}
@@ -0,0 +1,411 @@
+use std::{cell::RefCell, collections::{BTreeSet, HashMap, HashSet}, rc::Rc};
+
+use differential_dataflow::{
+    input::{Input, InputSession},
+    lattice::Lattice,
+    operators::{iterate::Variable, Threshold},
+    Collection,
+};
+use timely::{
+    communication::allocator::Thread,
+    dataflow::{ProbeHandle, Scope},
+    order::Product,
+    worker::Worker,
+    WorkerConfig,
+};
+
+use crate::{
+    ast::{Identifier, Program},
+    backend::{render::render_rule, well_founded, Change, EngineError, OutputBuffer, ProgramPlan, Semantics, Tuple},
+    semantic::{Stratification, SymbolTable},
+};
+
+/// A program compiled into a live timely dataflow.
+///
+/// Every relation gets an input session, so tuples can be inserted into or
+/// retracted from any relation, and every relation is observed so that its
+/// contents can be read back after each [`Engine::commit`].
+pub struct Engine {
+    worker: Worker<Thread>,
+    semantics: Semantics,
+    inputs: HashMap<Identifier, InputSession<u64, Tuple, isize>>,
+    outputs: HashMap<Identifier, Rc<RefCell<OutputBuffer>>>,
+    undefined: HashMap<Identifier, Rc<RefCell<OutputBuffer>>>,
+    probe: ProbeHandle<u64>,
+    epoch: u64,
+}
+
+impl Engine {
+    /// Compiles a stratified program, evaluating each recursive component of
+    /// the stratification to a fixpoint after the components it depends on.
+    pub fn new(program: &Program, symbols: &SymbolTable, stratification: &Stratification) -> Result<Engine, EngineError> {
+        let mut plan = ProgramPlan::new(program, symbols)?;
+        plan.iterations = stratification.recursive_components().cloned().collect();
+        Engine::build(plan, Semantics::Stratified)
+    }
+
+    /// Compiles a program under the well-founded semantics, which also gives a
+    /// meaning to programs with negation through recursion.
+    pub fn well_founded(program: &Program, symbols: &SymbolTable) -> Result<Engine, EngineError> {
+        Engine::build(ProgramPlan::new(program, symbols)?, Semantics::WellFounded)
+    }
+
+    fn build(plan: ProgramPlan, semantics: Semantics) -> Result<Engine, EngineError> {
+        let mut worker = Worker::new(WorkerConfig::default(), Thread::new());
+        let mut probe = ProbeHandle::new();
+        let mut inputs = HashMap::new();
+        let mut outputs = HashMap::new();
+        let mut undefined = HashMap::new();
+
+        worker.dataflow::<u64, _, _>(|scope| -> Result<(), EngineError> {
+            let mut collections = HashMap::new();
+            for relation in &plan.relations {
+                let (input, collection) = scope.new_collection::<Tuple, isize>();
+                inputs.insert(relation.clone(), input);
+                collections.insert(relation.clone(), collection);
+            }
+
+            let views = match semantics {
+                Semantics::Stratified => {
+                    let mut builder = RelationBuilder::new(scope.clone(), &plan, collections);
+                    plan.relations.iter()
+                        .map(|relation| Ok((relation.clone(), builder.relation(relation)?, None)))
+                        .collect::<Result<Vec<_>, EngineError>>()?
+                }
+                Semantics::WellFounded => well_founded::build(scope, &plan, &collections),
+            };
+
+            for (relation, known, unknown) in views {
+                if let Some(unknown) = unknown {
+                    undefined.insert(relation.clone(), observe(&unknown, &mut probe));
+                }
+                outputs.insert(relation, observe(&known, &mut probe));
+            }
+            Ok(())
+        })?;
+
+        let mut engine = Engine { worker, semantics, inputs, outputs, undefined, probe, epoch: 0 };
+        for (relation, tuple) in plan.facts {
+            engine.insert(&relation, tuple)?;
+        }
+        Ok(engine)
+    }
+
+    pub fn insert(&mut self, relation: &Identifier, tuple: Tuple) -> Result<(), EngineError> {
+        self.update(relation, tuple, 1)
+    }
+
+    pub fn update(&mut self, relation: &Identifier, tuple: Tuple, diff: isize) -> Result<(), EngineError> {
+        let input = self.inputs.get_mut(relation)
+            .ok_or_else(|| EngineError::new(format!("Unknown relation '{}'", relation)))?;
+        input.update(tuple, diff);
+        Ok(())
+    }
+
+    /// The epoch that updates currently go to.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Closes the current epoch and runs the dataflow until all of its
+    /// consequences have been computed.
+    pub fn commit(&mut self) {
+        self.advance_to(self.epoch + 1);
+    }
+
+    /// Closes every epoch before `epoch`, which must not be earlier than the
+    /// current one, and runs the dataflow until their consequences have been
+    /// computed. Later updates go to `epoch`.
+    pub fn advance_to(&mut self, epoch: u64) {
+        self.epoch = epoch;
+        for input in self.inputs.values_mut() {
+            input.advance_to(self.epoch);
+            input.flush();
+        }
+        while self.probe.less_than(&self.epoch) {
+            self.worker.step();
+        }
+    }
+
+    /// The tuples of `relation` that are (known to be) true.
+    pub fn tuples(&self, relation: &Identifier) -> Result<Vec<Tuple>, EngineError> {
+        self.outputs.get(relation)
+            .map(|buffer| buffer.borrow().tuples())
+            .ok_or_else(|| EngineError::new(format!("Unknown relation '{}'", relation)))
+    }
+
+    /// The tuples of `relation` that are neither true nor false under the
+    /// well-founded semantics, or `None` when evaluating stratified negation.
+    pub fn undefined_tuples(&self, relation: &Identifier) -> Result<Option<Vec<Tuple>>, EngineError> {
+        self.tuples(relation)?;
+        Ok(match self.semantics {
+            Semantics::Stratified => None,
+            Semantics::WellFounded => Some(
+                self.undefined.get(relation).map(|buffer| buffer.borrow().tuples()).unwrap_or_default()
+            ),
+        })
+    }
+
+    /// Records the changes to `relation`, including those to its undefined
+    /// tuples, from the next commit on.
+    pub fn track_changes(&mut self, relation: &Identifier) -> Result<(), EngineError> {
+        let buffer = self.outputs.get(relation)
+            .ok_or_else(|| EngineError::new(format!("Unknown relation '{}'", relation)))?;
+        buffer.borrow_mut().track_changes();
+        if let Some(buffer) = self.undefined.get(relation) {
+            buffer.borrow_mut().track_changes();
+        }
+        Ok(())
+    }
+
+    /// The changes to the (known) tuples of `relation` since the last call, if
+    /// [`Engine::track_changes`] was called for it.
+    pub fn take_changes(&self, relation: &Identifier) -> Result<Vec<Change>, EngineError> {
+        self.outputs.get(relation)
+            .map(|buffer| buffer.borrow_mut().take_changes())
+            .ok_or_else(|| EngineError::new(format!("Unknown relation '{}'", relation)))
+    }
+
+    /// The changes to the undefined tuples of `relation` since the last call,
+    /// or `None` when evaluating stratified negation.
+    pub fn take_undefined_changes(&self, relation: &Identifier) -> Result<Option<Vec<Change>>, EngineError> {
+        self.tuples(relation)?;
+        Ok(match self.semantics {
+            Semantics::Stratified => None,
+            Semantics::WellFounded => Some(
+                self.undefined.get(relation).map(|buffer| buffer.borrow_mut().take_changes()).unwrap_or_default()
+            ),
+        })
+    }
+}
+
+/// Accumulates the contents of `collection` into a buffer the engine can read.
+fn observe<G>(collection: &Collection<G, Tuple>, probe: &mut ProbeHandle<u64>) -> Rc<RefCell<OutputBuffer>>
+where
+    G: Scope<Timestamp = u64>,
+{
+    let buffer = Rc::new(RefCell::new(OutputBuffer::default()));
+    let sink = buffer.clone();
+
+    collection
+        .inspect(move |(tuple, time, diff)| sink.borrow_mut().update(tuple.clone(), *time, *diff))
+        .probe_with(probe);
+
+    buffer
+}
+
+/// Builds the collection of each relation on demand, after the relations it depends on.
+struct RelationBuilder<'p, G: Scope> {
+    scope: G,
+    plan: &'p ProgramPlan,
+    inputs: HashMap<Identifier, Collection<G, Tuple>>,
+    built: HashMap<Identifier, Collection<G, Tuple>>,
+    visiting: HashSet<Identifier>,
+}
+
+impl<'p, G: Scope> RelationBuilder<'p, G>
+where
+    G::Timestamp: Lattice + Ord,
+{
+    fn new(scope: G, plan: &'p ProgramPlan, inputs: HashMap<Identifier, Collection<G, Tuple>>) -> Self {
+        RelationBuilder { scope, plan, inputs, built: HashMap::new(), visiting: HashSet::new() }
+    }
+
+    /// Guards against a cycle outside of the plan's iterations, which the
+    /// semantic checks rule out but a stratification built elsewhere might not.
+    fn visit(&mut self, name: &Identifier) -> Result<(), EngineError> {
+        if self.visiting.insert(name.clone()) {
+            Ok(())
+        } else {
+            Err(EngineError::new(format!(
+                "Relation '{}' is recursive but not part of a recursive component of the stratification", name
+            )))
+        }
+    }
+
+    fn relation(&mut self, name: &Identifier) -> Result<Collection<G, Tuple>, EngineError> {
+        if let Some(collection) = self.built.get(name) {
+            return Ok(collection.clone());
+        }
+        if let Some(block) = self.plan.iteration_of(name) {
+            self.iteration(block)?;
+            return Ok(self.built[name].clone());
+        }
+        self.visit(name)?;
+
+        for rule in self.plan.rules_for(name) {
+            for dependency in rule.dependencies() {
+                self.relation(dependency)?;
+            }
+        }
+
+        let mut collection = self.inputs[name].clone();
+        for rule in self.plan.rules_for(name) {
+            let lookup = |relation: &Identifier| self.built[relation].clone();
+            let derived = render_rule(rule, lookup, lookup);
+            collection = collection.concat(&derived);
+        }
+        let collection = collection.distinct();
+
+        self.visiting.remove(name);
+        self.built.insert(name.clone(), collection.clone());
+        Ok(collection)
+    }
+
+    /// Evaluates a group of mutually recursive relations to a fixpoint in a
+    /// nested scope, with one `Variable` per relation of the group. Relations
+    /// from outside the group are entered into the scope unchanged.
+    fn iteration(&mut self, block: &BTreeSet<Identifier>) -> Result<(), EngineError> {
+        for name in block {
+            self.visit(name)?;
+        }
+
+        let mut external = BTreeSet::new();
+        for name in block {
+            for rule in self.plan.rules_for(name) {
+                external.extend(rule.dependencies().into_iter().filter(|d| !block.contains(*d)));
+            }
+        }
+        for dependency in &external {
+            self.relation(dependency)?;
+        }
+
+        let results = self.scope.iterative::<u32, _, _>(|inner| {
+            let entered: HashMap<_, _> = external.iter()
+                .map(|name| (*name, self.built[*name].enter(inner)))
+                .collect();
+            let mut variables: HashMap<_, _> = block.iter()
+                .map(|name| (name, Variable::new(inner, Product::new(Default::default(), 1))))
+                .collect();
+
+            let mut results = Vec::new();
+            for name in block {
+                let mut collection = self.inputs[name].enter(inner);
+                for rule in self.plan.rules_for(name) {
+                    let lookup = |relation: &Identifier| match variables.get(relation) {
+                        Some(variable) => (**variable).clone(),
+                        None => entered[relation].clone(),
+                    };
+                    let derived = render_rule(rule, lookup, lookup);
+                    collection = collection.concat(&derived);
+                }
+                results.push((name, collection.distinct()));
+            }
+
+            results.into_iter()
+                .map(|(name, collection)| {
+                    let variable = variables.remove(name).unwrap();
+                    (name.clone(), variable.set(&collection).leave())
+                })
+                .collect::<Vec<_>>()
+        });
+
+        for (name, collection) in results {
+            self.visiting.remove(&name);
+            self.built.insert(name, collection);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        ast::Parser,
+        backend::Value,
+        lexer::{Lexer, Span},
+        semantic::{check_aggregates, check_recursion, check_safety, infer_types, SemanticError},
+    };
+
+    /// Compiles `source`, which must pass the semantic checks, and commits its facts.
+    pub(crate) fn compile(source: &str, semantics: Semantics) -> Engine {
+        compile_program(source, semantics).2
+    }
+
+    /// Like [`compile`], also returning the program and its symbol table.
+    pub(crate) fn compile_program(source: &str, semantics: Semantics) -> (Program, SymbolTable, Engine) {
+        let fail = |errors: Vec<SemanticError>| -> ! {
+            panic!("{}", errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))
+        };
+
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (program, errors) = Program::parse_all(&mut Parser::new(source, tokens));
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let mut symbols = SymbolTable::new(&program, source).unwrap_or_else(|e| fail(e));
+        check_aggregates(&program, source).unwrap_or_else(|e| fail(e));
+        infer_types(&program, &mut symbols, source).unwrap_or_else(|e| fail(e));
+        check_safety(&program, source).unwrap_or_else(|e| fail(e));
+
+        let engine = match semantics {
+            Semantics::Stratified => {
+                let stratification = Stratification::new(&program, source).unwrap_or_else(|e| fail(e));
+                Engine::new(&program, &symbols, &stratification)
+            }
+            Semantics::WellFounded => {
+                check_recursion(&program, source).unwrap_or_else(|e| fail(e));
+                Engine::well_founded(&program, &symbols)
+            }
+        };
+        let mut engine = engine.unwrap();
+        engine.commit();
+        (program, symbols, engine)
+    }
+
+    pub(crate) fn relation(name: &str) -> Identifier {
+        let span = Span { start_offset: 0, end_offset: 1, start_line: 1, start_column: 1, end_line: 1, end_column: 1 };
+        Identifier::new(name, span)
+    }
+
+    /// Each tuple as it would be written in a fact, e.g. `1, "a"`.
+    pub(crate) fn rows(tuples: Vec<Tuple>) -> Vec<String> {
+        tuples.iter()
+            .map(|tuple| tuple.iter().map(Value::to_source).collect::<Vec<_>>().join(", "))
+            .collect()
+    }
+
+    /// The tuples of `name` that are (known to be) true.
+    pub(crate) fn tuples(engine: &Engine, name: &str) -> Vec<String> {
+        rows(engine.tuples(&relation(name)).unwrap())
+    }
+
+    #[test]
+    fn joins_atoms_on_their_shared_variables() {
+        let engine = compile(
+            "Parent(\"ann\", \"bob\"). Parent(\"bob\", \"cid\"). Parent(\"bob\", \"dan\"). Parent(\"eve\", \"fay\").\n\
+             Grandparent(x, z) :- Parent(x, y), Parent(y, z).\n",
+            Semantics::Stratified,
+        );
+        assert_eq!(tuples(&engine, "Grandparent"), vec!["\"ann\", \"cid\"", "\"ann\", \"dan\""]);
+    }
+
+    #[test]
+    fn iterates_a_transitive_closure_to_a_fixpoint() {
+        let mut engine = compile(
+            "Edge(1, 2). Edge(2, 3). Edge(3, 1). Edge(4, 5).\n\
+             .iterate {\n  Path(x, y) :- Edge(x, y).\n  Path(x, z) :- Path(x, y), Edge(y, z).\n}\n",
+            Semantics::Stratified,
+        );
+        assert_eq!(tuples(&engine, "Path"), vec![
+            "1, 1", "1, 2", "1, 3", "2, 1", "2, 2", "2, 3", "3, 1", "3, 2", "3, 3", "4, 5",
+        ]);
+
+        engine.update(&relation("Edge"), vec![Value::Integer(3), Value::Integer(1)], -1).unwrap();
+        engine.commit();
+        assert_eq!(tuples(&engine, "Path"), vec!["1, 2", "1, 3", "2, 3", "4, 5"]);
+    }
+
+    #[test]
+    fn negation_removes_the_matching_tuples() {
+        let engine = compile(
+            "Student(1). Student(2). Student(3).\nEnrolled(2, \"cs101\"). Enrolled(3, \"ma201\").\n\
+             Idle(x) :- Student(x), not Enrolled(x, _).\n\
+             NotInCs(x) :- Student(x), !Enrolled(x, \"cs101\").\n",
+            Semantics::Stratified,
+        );
+        assert_eq!(tuples(&engine, "Idle"), vec!["1"]);
+        assert_eq!(tuples(&engine, "NotInCs"), vec!["1", "3"]);
+    }
+}
@@ -0,0 +1,36 @@
+use crate::{diagnostics::{Code, Diagnostic}, io::IoError};
+
+#[derive(Debug)]
+pub struct EngineError {
+    pub code: Code,
+    pub message: String,
+}
+
+impl EngineError {
+    pub fn new(message: impl Into<String>) -> Self {
+        EngineError { code: Code::EngineError, message: message.into() }
+    }
+
+    /// An error reading or writing a file or stream.
+    pub fn io(message: impl Into<String>) -> Self {
+        EngineError { code: Code::IoError, message: message.into() }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.code, self.message.clone())
+    }
+}
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Engine Error: {}.", self.message)
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<IoError> for EngineError {
+    fn from(err: IoError) -> Self {
+        EngineError::io(err.to_string())
+    }
+}
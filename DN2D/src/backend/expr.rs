@@ -0,0 +1,103 @@
+use std::cmp::Ordering;
+
+use crate::{ast::{BinaryOperator, UnaryOperator}, backend::{value::Float, Value}};
+
+/// An expression whose variables have been resolved to positions in the row of
+/// bound variables, so that it can be moved into dataflow operators.
+#[derive(Debug, Clone)]
+pub enum ScalarExpr {
+    Constant(Value),
+    Column(usize),
+    Binary {
+        left: Box<ScalarExpr>,
+        op: BinaryOperator,
+        right: Box<ScalarExpr>,
+    },
+    Unary {
+        op: UnaryOperator,
+        expr: Box<ScalarExpr>,
+    },
+}
+
+impl ScalarExpr {
+    /// Evaluates the expression against `row`.
+    ///
+    /// Returns `None` when the expression has no value for this row (type mismatch,
+    /// division by zero, overflow), in which case the row is dropped.
+    pub fn eval(&self, row: &[Value]) -> Option<Value> {
+        match self {
+            ScalarExpr::Constant(value) => Some(value.clone()),
+            ScalarExpr::Column(index) => row.get(*index).cloned(),
+            ScalarExpr::Unary { op: UnaryOperator::Neg, expr } => match expr.eval(row)? {
+                Value::Integer(i) => i.checked_neg().map(Value::Integer),
+                Value::Float(f) => Some(Value::Float(Float(-f.0))),
+                _ => None,
+            },
+            ScalarExpr::Binary { left, op, right } => {
+                binary(*op, left.eval(row)?, right.eval(row)?)
+            }
+        }
+    }
+
+    /// Evaluates the expression as a filter predicate.
+    pub fn is_true(&self, row: &[Value]) -> bool {
+        matches!(self.eval(row), Some(Value::Boolean(true)))
+    }
+}
+
+/// Applies a binary operator to two values, as [`ScalarExpr::eval`] does.
+pub fn binary(op: BinaryOperator, left: Value, right: Value) -> Option<Value> {
+    match op {
+        BinaryOperator::Add
+        | BinaryOperator::Sub
+        | BinaryOperator::Mul
+        | BinaryOperator::Div
+        | BinaryOperator::Mod => arithmetic(op, left, right),
+        BinaryOperator::Eq => Some(Value::Boolean(compare(&left, &right) == Some(Ordering::Equal))),
+        BinaryOperator::NotEq => Some(Value::Boolean(compare(&left, &right) != Some(Ordering::Equal))),
+        BinaryOperator::Lt => compare(&left, &right).map(|o| Value::Boolean(o.is_lt())),
+        BinaryOperator::LtEq => compare(&left, &right).map(|o| Value::Boolean(o.is_le())),
+        BinaryOperator::Gt => compare(&left, &right).map(|o| Value::Boolean(o.is_gt())),
+        BinaryOperator::GtEq => compare(&left, &right).map(|o| Value::Boolean(o.is_ge())),
+    }
+}
+
+fn arithmetic(op: BinaryOperator, left: Value, right: Value) -> Option<Value> {
+    match (left, right) {
+        (Value::Integer(l), Value::Integer(r)) => match op {
+            BinaryOperator::Add => l.checked_add(r),
+            BinaryOperator::Sub => l.checked_sub(r),
+            BinaryOperator::Mul => l.checked_mul(r),
+            BinaryOperator::Div => l.checked_div(r),
+            BinaryOperator::Mod => l.checked_rem(r),
+            _ => None,
+        }.map(Value::Integer),
+        (Value::String(l), Value::String(r)) if matches!(op, BinaryOperator::Add) => {
+            Some(Value::String(l + &r))
+        }
+        (l, r) => {
+            let (l, r) = (l.as_f64()?, r.as_f64()?);
+            let result = match op {
+                BinaryOperator::Add => l + r,
+                BinaryOperator::Sub => l - r,
+                BinaryOperator::Mul => l * r,
+                BinaryOperator::Div if r != 0.0 => l / r,
+                BinaryOperator::Mod if r != 0.0 => l % r,
+                _ => return None,
+            };
+            Some(Value::Float(Float(result)))
+        }
+    }
+}
+
+/// Orders two values, promoting integers to floats when compared with floats.
+/// Values of unrelated types are incomparable.
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Integer(_), Value::Float(_)) | (Value::Float(_), Value::Integer(_)) => {
+            Some(Float(left.as_f64()?).cmp(&Float(right.as_f64()?)))
+        }
+        _ if std::mem::discriminant(left) == std::mem::discriminant(right) => Some(left.cmp(right)),
+        _ => None,
+    }
+}
@@ -0,0 +1,24 @@
+pub mod plan;
+pub mod expr;
+pub mod aggregate;
+pub mod value;
+pub mod engine;
+pub mod output;
+pub mod render;
+pub mod runner;
+pub mod engine_error;
+pub mod semantics;
+pub mod well_founded;
+pub mod watch;
+pub mod repl;
+
+pub use engine::Engine;
+pub use runner::run;
+pub use watch::watch;
+pub use repl::repl;
+pub use expr::ScalarExpr;
+pub use plan::ProgramPlan;
+pub use value::{Tuple, Value};
+pub use output::{Change, OutputBuffer};
+pub use engine_error::EngineError;
+pub use semantics::Semantics;
@@ -0,0 +1,63 @@
+use std::collections::{btree_map::Entry, BTreeMap};
+
+use crate::backend::Tuple;
+
+/// An insertion (`diff` 1) or retraction (`diff` -1) of a tuple at an epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub tuple: Tuple,
+    pub epoch: u64,
+    pub diff: isize,
+}
+
+/// The accumulated contents of a relation, as observed at the end of the dataflow.
+#[derive(Debug, Default)]
+pub struct OutputBuffer {
+    contents: BTreeMap<Tuple, isize>,
+    /// The changes not yet taken, if they are being tracked.
+    changes: Option<Vec<Change>>,
+}
+
+impl OutputBuffer {
+    pub fn update(&mut self, tuple: Tuple, epoch: u64, diff: isize) {
+        if let Some(changes) = &mut self.changes {
+            changes.push(Change { tuple: tuple.clone(), epoch, diff });
+        }
+
+        match self.contents.entry(tuple) {
+            Entry::Vacant(entry) => {
+                entry.insert(diff);
+            }
+            Entry::Occupied(mut entry) => {
+                *entry.get_mut() += diff;
+                if *entry.get() == 0 {
+                    entry.remove();
+                }
+            }
+        }
+    }
+
+    /// The tuples currently in the relation, in sorted order.
+    pub fn tuples(&self) -> Vec<Tuple> {
+        self.contents.keys().cloned().collect()
+    }
+
+    /// Starts recording the changes to the relation.
+    pub fn track_changes(&mut self) {
+        self.changes.get_or_insert_with(Vec::new);
+    }
+
+    /// The changes recorded since the last call, ordered by epoch and then by
+    /// tuple, with the changes to a tuple within an epoch summed up.
+    pub fn take_changes(&mut self) -> Vec<Change> {
+        let mut consolidated: BTreeMap<(u64, Tuple), isize> = BTreeMap::new();
+        for Change { tuple, epoch, diff } in self.changes.as_mut().map(std::mem::take).unwrap_or_default() {
+            *consolidated.entry((epoch, tuple)).or_default() += diff;
+        }
+
+        consolidated.into_iter()
+            .filter(|(_, diff)| *diff != 0)
+            .map(|((epoch, tuple), diff)| Change { tuple, epoch, diff })
+            .collect()
+    }
+}
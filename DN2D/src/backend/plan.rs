@@ -0,0 +1,507 @@
+use std::{cmp::Ordering, collections::{BTreeSet, HashMap}};
+
+use crate::{
+    ast::{Atom, BinaryOperator, Expression, Fact, Identifier, Literal, Program, Query, Rule, RuleOrFact, Statement},
+    backend::{aggregate::AggregatePlan, expr::compare, EngineError, ScalarExpr, Tuple, Value},
+    semantic::{SymbolTable, Type},
+};
+
+/// The selection an atom applies to the tuples of its relation before they are
+/// joined: constant terms, variables the body equates with a constant, and
+/// variables repeated within the same atom.
+#[derive(Debug, Clone)]
+pub struct AtomPlan {
+    pub relation: Identifier,
+    pub arity: usize,
+    pub constants: Vec<(usize, Value)>,
+    pub equalities: Vec<(usize, usize)>,
+}
+
+impl AtomPlan {
+    pub fn matches(&self, tuple: &[Value]) -> bool {
+        tuple.len() == self.arity
+            && self.constants.iter().all(|(column, value)| compare(&tuple[*column], value) == Some(Ordering::Equal))
+            && self.equalities.iter().all(|(a, b)| tuple[*a] == tuple[*b])
+    }
+}
+
+/// The first positive atom of a rule body, which seeds the row of bound variables.
+#[derive(Debug, Clone)]
+pub struct Scan {
+    pub atom: AtomPlan,
+    pub columns: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Joins the bound rows with an atom on the variables they share, appending
+    /// the atom's newly bound variables to each row.
+    Join {
+        atom: AtomPlan,
+        bound_keys: Vec<usize>,
+        atom_keys: Vec<usize>,
+        new_columns: Vec<usize>,
+    },
+    /// Keeps the bound rows that have no match in a negated atom, which belongs
+    /// to a lower stratum than the rule's head.
+    Antijoin {
+        atom: AtomPlan,
+        bound_keys: Vec<usize>,
+        atom_keys: Vec<usize>,
+    },
+    /// Keeps the bound rows for which a `Literal::Condition` holds.
+    Filter(ScalarExpr),
+}
+
+#[derive(Debug, Clone)]
+pub enum HeadTerm {
+    Scalar(ScalarExpr),
+    Aggregate(AggregatePlan),
+}
+
+#[derive(Debug, Clone)]
+pub struct RulePlan {
+    pub head: Identifier,
+    pub scan: Scan,
+    pub steps: Vec<Step>,
+    pub projection: Vec<HeadTerm>,
+    /// The type of each column of the head, if known, which the values the
+    /// rule derives are converted to.
+    pub types: Vec<Option<Type>>,
+}
+
+impl RulePlan {
+    /// Whether the head aggregates the body's bindings, grouped by its scalar terms.
+    pub fn is_aggregation(&self) -> bool {
+        self.projection.iter().any(|term| matches!(term, HeadTerm::Aggregate(_)))
+    }
+
+    /// The relations the rule body reads from.
+    pub fn dependencies(&self) -> Vec<&Identifier> {
+        let mut dependencies = vec![&self.scan.atom.relation];
+        for step in &self.steps {
+            if let Step::Join { atom, .. } | Step::Antijoin { atom, .. } = step {
+                dependencies.push(&atom.relation);
+            }
+        }
+        dependencies
+    }
+}
+
+/// Everything the dataflow needs to know about a program, independent of any scope.
+#[derive(Debug, Default)]
+pub struct ProgramPlan {
+    pub relations: BTreeSet<Identifier>,
+    pub rules: Vec<RulePlan>,
+    pub facts: Vec<(Identifier, Tuple)>,
+    /// The groups of mutually recursive relations, each of which is evaluated
+    /// to a fixpoint together.
+    pub iterations: Vec<BTreeSet<Identifier>>,
+}
+
+impl ProgramPlan {
+    /// Plans `program`, whose column types `symbols` holds.
+    pub fn new(program: &Program, symbols: &SymbolTable) -> Result<ProgramPlan, EngineError> {
+        let mut plan = ProgramPlan::default();
+        let mut queries = 0;
+
+        for statement in &program.statements {
+            match statement {
+                Statement::Decl(decl) => {
+                    plan.relations.insert(decl.name.clone());
+                }
+                Statement::Read(read) => {
+                    plan.relations.insert(read.name.clone());
+                }
+                Statement::Write(write) => {
+                    plan.relations.insert(write.name.clone());
+                }
+                Statement::Rule(rule) => plan.add_rule(rule, symbols)?,
+                Statement::Fact(fact) => plan.add_fact(fact, symbols)?,
+                Statement::Iterate(block) => {
+                    for rule_or_fact in &block.rules {
+                        match rule_or_fact {
+                            RuleOrFact::Rule(rule) => plan.add_rule(rule, symbols)?,
+                            RuleOrFact::Fact(fact) => plan.add_fact(fact, symbols)?,
+                        }
+                    }
+                }
+                Statement::Query(query) => {
+                    plan.add_query(query_relation(queries, query), query)?;
+                    queries += 1;
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    /// The group of mutually recursive relations `relation` belongs to, if any.
+    pub fn iteration_of(&self, relation: &Identifier) -> Option<&BTreeSet<Identifier>> {
+        self.iterations.iter().find(|block| block.contains(relation))
+    }
+
+    pub fn rules_for<'a>(&'a self, relation: &'a Identifier) -> impl Iterator<Item = &'a RulePlan> {
+        self.rules.iter().filter(move |rule| &rule.head == relation)
+    }
+
+    fn add_rule(&mut self, rule: &Rule, symbols: &SymbolTable) -> Result<(), EngineError> {
+        self.relations.insert(rule.head.name.clone());
+        for literal in &rule.body {
+            if let Literal::Positive(atom) | Literal::Negative(atom) = literal {
+                self.relations.insert(atom.name.clone());
+            }
+        }
+        self.rules.push(RulePlanner::default().plan(&rule.head, &rule.body, column_types(symbols, &rule.head.name))?);
+        Ok(())
+    }
+
+    /// Plans a query as an anonymous rule deriving `relation`, with one column
+    /// per variable of the query.
+    fn add_query(&mut self, relation: Identifier, query: &Query) -> Result<(), EngineError> {
+        let head = Atom {
+            name: relation,
+            terms: query.variables().into_iter().map(|variable| Expression::Variable(variable.clone())).collect(),
+            span: query.span,
+        };
+
+        self.relations.insert(head.name.clone());
+        for literal in &query.body {
+            if let Literal::Positive(atom) | Literal::Negative(atom) = literal {
+                self.relations.insert(atom.name.clone());
+            }
+        }
+        self.rules.push(RulePlanner::default().plan(&head, &query.body, &[])?);
+        Ok(())
+    }
+
+    /// Adds a fact, with its values converted to the types of their columns
+    /// as the values read from a file are.
+    fn add_fact(&mut self, fact: &Fact, symbols: &SymbolTable) -> Result<(), EngineError> {
+        let types = column_types(symbols, &fact.head.name);
+        let tuple = fact.head.terms.iter()
+            .enumerate()
+            .map(|(column, term)| {
+                let value = fold_constant(term).ok_or_else(|| EngineError::new(format!(
+                    "Fact '{}' must only contain constant values", fact.head.name
+                )))?;
+                match types.get(column).copied().flatten() {
+                    Some(ty) => value.clone().conform(ty).ok_or_else(|| EngineError::new(format!(
+                        "Fact '{}' has {} in column {}, expected {}", fact.head.name, value, column + 1, ty.with_article()
+                    ))),
+                    None => Ok(value),
+                }
+            })
+            .collect::<Result<Tuple, _>>()?;
+
+        self.relations.insert(fact.head.name.clone());
+        self.facts.push((fact.head.name.clone(), tuple));
+        Ok(())
+    }
+}
+
+/// The type of each column of `relation`, or none if it is unknown.
+fn column_types<'a>(symbols: &'a SymbolTable, relation: &Identifier) -> &'a [Option<Type>] {
+    symbols.relations.get(relation).map(|schema| schema.types.as_slice()).unwrap_or_default()
+}
+
+/// The relation that collects the answers to a query; `index` counts the
+/// queries of the program from 0. The name cannot clash with a relation of
+/// the program, as it is not an identifier.
+pub fn query_relation(index: usize, query: &Query) -> Identifier {
+    Identifier::new(format!("query#{}", index + 1), query.span)
+}
+
+/// Evaluates an expression that does not reference any variable.
+pub fn fold_constant(expr: &Expression) -> Option<Value> {
+    match expr {
+        Expression::Constant(constant, _) => Some(Value::from(constant)),
+        Expression::Wildcard(_) | Expression::Aggregate(_) => None,
+        _ if expr.variables().is_empty() => compile_expr(expr, &HashMap::new()).ok()?.eval(&[]),
+        _ => None,
+    }
+}
+
+fn unparen(expr: &Expression) -> &Expression {
+    match expr {
+        Expression::Paren(expr, _) => unparen(expr),
+        expr => expr,
+    }
+}
+
+/// Compiles an expression over the variables bound so far in a rule body.
+pub fn compile_expr(expr: &Expression, bound: &HashMap<Identifier, usize>) -> Result<ScalarExpr, EngineError> {
+    match expr {
+        Expression::Constant(constant, _) => Ok(ScalarExpr::Constant(Value::from(constant))),
+        Expression::Variable(name) => bound.get(name)
+            .map(|index| ScalarExpr::Column(*index))
+            .ok_or_else(|| EngineError::new(format!("Variable '{}' is not bound by a positive atom", name))),
+        Expression::Wildcard(_) => Err(EngineError::new("The wildcard '_' can only be used as an atom term")),
+        Expression::Aggregate(aggregate) => Err(EngineError::new(format!(
+            "Aggregate '{}' can only be used as a term of a rule head", aggregate.func
+        ))),
+        Expression::Binary { left, op, right } => Ok(ScalarExpr::Binary {
+            left: Box::new(compile_expr(left, bound)?),
+            op: *op,
+            right: Box::new(compile_expr(right, bound)?),
+        }),
+        Expression::Unary { op, expr, .. } => Ok(ScalarExpr::Unary {
+            op: *op,
+            expr: Box::new(compile_expr(expr, bound)?),
+        }),
+        Expression::Paren(expr, _) => compile_expr(expr, bound),
+    }
+}
+
+/// Decides the join order of a rule body (left to right) and tracks where each
+/// variable lives in the row of bound variables.
+#[derive(Default)]
+struct RulePlanner {
+    bound: HashMap<Identifier, usize>,
+    /// Variables that a condition of the body equates with a constant, which
+    /// atoms select on directly instead of filtering the joined rows.
+    pinned: HashMap<Identifier, Value>,
+}
+
+/// How the terms of a body atom relate to the variables bound before it.
+struct AtomBinding {
+    atom: AtomPlan,
+    bound_keys: Vec<usize>,
+    atom_keys: Vec<usize>,
+    new_columns: Vec<usize>,
+}
+
+impl RulePlanner {
+    fn plan(mut self, head: &Atom, body: &[Literal], types: &[Option<Type>]) -> Result<RulePlan, EngineError> {
+        let mut positives = Vec::new();
+        let mut filters = Vec::new();
+
+        for literal in body {
+            match literal {
+                Literal::Positive(atom) => positives.push(atom),
+                Literal::Condition(condition) if self.pin(condition) => {}
+                Literal::Negative(_) | Literal::Condition(_) => filters.push(literal),
+            }
+        }
+
+        let Some((first, rest)) = positives.split_first() else {
+            return Err(EngineError::new(format!(
+                "Rule for '{}' must contain at least one positive atom in its body", head.name
+            )));
+        };
+
+        let binding = self.bind_atom(first)?;
+        let scan = Scan { atom: binding.atom, columns: binding.new_columns };
+        let mut steps = Vec::new();
+        self.push_ready_filters(&mut filters, &mut steps)?;
+
+        for atom in rest {
+            let AtomBinding { atom, bound_keys, atom_keys, new_columns } = self.bind_atom(atom)?;
+            steps.push(Step::Join { atom, bound_keys, atom_keys, new_columns });
+            self.push_ready_filters(&mut filters, &mut steps)?;
+        }
+
+        // A filter still pending references a variable no positive atom binds.
+        if let Some(literal) = filters.first() {
+            return Err(match literal {
+                Literal::Negative(atom) => self.negate_atom(atom).unwrap_err(),
+                Literal::Condition(condition) => compile_expr(condition, &self.bound).unwrap_err(),
+                Literal::Positive(_) => unreachable!(),
+            });
+        }
+
+        let projection = head.terms.iter()
+            .map(|term| match term {
+                Expression::Aggregate(aggregate) => Ok(HeadTerm::Aggregate(AggregatePlan {
+                    func: aggregate.func,
+                    // `count()` counts the bindings themselves, whatever the argument.
+                    arg: match &aggregate.arg {
+                        Some(arg) => compile_expr(arg, &self.bound)?,
+                        None => ScalarExpr::Constant(Value::Boolean(true)),
+                    },
+                })),
+                _ => compile_expr(term, &self.bound).map(HeadTerm::Scalar),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(RulePlan { head: head.name.clone(), scan, steps, projection, types: types.to_vec() })
+    }
+
+    /// Records the constant of a `variable == constant` condition, returning
+    /// whether the condition is thereby taken care of.
+    fn pin(&mut self, condition: &Expression) -> bool {
+        let Expression::Binary { left, op: BinaryOperator::Eq, right } = unparen(condition) else {
+            return false;
+        };
+        let (variable, value) = match (unparen(left), unparen(right)) {
+            (Expression::Variable(variable), constant) | (constant, Expression::Variable(variable)) => {
+                match fold_constant(constant) {
+                    Some(value) => (variable, value),
+                    None => return false,
+                }
+            }
+            _ => return false,
+        };
+
+        if self.pinned.contains_key(variable) {
+            return false;
+        }
+        self.pinned.insert(variable.clone(), value);
+        true
+    }
+
+    /// Emits a step for every pending negated atom or condition whose variables
+    /// are all bound, so rows are discarded as early as possible.
+    fn push_ready_filters(&self, filters: &mut Vec<&Literal>, steps: &mut Vec<Step>) -> Result<(), EngineError> {
+        let mut pending = Vec::new();
+        for literal in filters.drain(..) {
+            let variables = match literal {
+                Literal::Negative(atom) => atom.terms.iter().flat_map(|term| term.variables()).collect(),
+                Literal::Condition(condition) => condition.variables(),
+                Literal::Positive(_) => unreachable!(),
+            };
+
+            if !variables.iter().all(|v| self.bound.contains_key(*v)) {
+                pending.push(literal);
+                continue;
+            }
+
+            steps.push(match literal {
+                Literal::Negative(atom) => self.negate_atom(atom)?,
+                Literal::Condition(condition) => Step::Filter(compile_expr(condition, &self.bound)?),
+                Literal::Positive(_) => unreachable!(),
+            });
+        }
+        *filters = pending;
+        Ok(())
+    }
+
+    fn negate_atom(&self, atom: &Atom) -> Result<Step, EngineError> {
+        let AtomBinding { atom: plan, bound_keys, atom_keys, new_columns } = self.analyze_atom(atom)?;
+
+        if let Some(column) = new_columns.first() {
+            return Err(EngineError::new(format!(
+                "Variable '{}' in negated atom 'not {}(..)' is not bound by a positive atom",
+                atom.terms[*column].variables()[0], atom.name
+            )));
+        }
+        Ok(Step::Antijoin { atom: plan, bound_keys, atom_keys })
+    }
+
+    fn bind_atom(&mut self, atom: &Atom) -> Result<AtomBinding, EngineError> {
+        let binding = self.analyze_atom(atom)?;
+
+        for column in &binding.new_columns {
+            if let Expression::Variable(name) = &atom.terms[*column] {
+                self.bound.insert(name.clone(), self.bound.len());
+            }
+        }
+        Ok(binding)
+    }
+
+    fn analyze_atom(&self, atom: &Atom) -> Result<AtomBinding, EngineError> {
+        let mut binding = AtomBinding {
+            atom: AtomPlan {
+                relation: atom.name.clone(),
+                arity: atom.terms.len(),
+                constants: Vec::new(),
+                equalities: Vec::new(),
+            },
+            bound_keys: Vec::new(),
+            atom_keys: Vec::new(),
+            new_columns: Vec::new(),
+        };
+        let mut local: HashMap<&Identifier, usize> = HashMap::new();
+
+        for (column, term) in atom.terms.iter().enumerate() {
+            match term {
+                Expression::Wildcard(_) => {}
+                Expression::Variable(name) => {
+                    if let (false, Some(value)) = (local.contains_key(name), self.pinned.get(name)) {
+                        binding.atom.constants.push((column, value.clone()));
+                    }
+
+                    if let Some(first) = local.get(name) {
+                        binding.atom.equalities.push((*first, column));
+                    } else if let Some(index) = self.bound.get(name) {
+                        binding.bound_keys.push(*index);
+                        binding.atom_keys.push(column);
+                        local.insert(name, column);
+                    } else {
+                        binding.new_columns.push(column);
+                        local.insert(name, column);
+                    }
+                }
+                _ => {
+                    let value = fold_constant(term).ok_or_else(|| EngineError::new(format!(
+                        "Atom '{}' in a rule body may only contain variables, constants and wildcards", atom.name
+                    )))?;
+                    binding.atom.constants.push((column, value));
+                }
+            }
+        }
+
+        Ok(binding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{
+        engine::tests::{compile_program, rows, tuples},
+        Semantics,
+    };
+
+    /// The plan of the last rule or query of `source`.
+    fn plan_last(source: &str) -> RulePlan {
+        let (program, symbols, _) = compile_program(source, Semantics::Stratified);
+        ProgramPlan::new(&program, &symbols).unwrap().rules.pop().unwrap()
+    }
+
+    fn constants(atom: &AtomPlan) -> Vec<(usize, Value)> {
+        atom.constants.clone()
+    }
+
+    #[test]
+    fn pushes_variables_equated_with_constants_into_every_atom() {
+        let plan = plan_last("Edge(1, 2). Edge(2, 3).\nPath(x, z) :- Edge(x, y), Edge(y, z), y == 2.\n");
+        assert_eq!(constants(&plan.scan.atom), vec![(1, Value::Integer(2))]);
+        let [Step::Join { atom, .. }] = plan.steps.as_slice() else {
+            panic!("Expected a single join but found {:?}", plan.steps);
+        };
+        assert_eq!(constants(atom), vec![(0, Value::Integer(2))]);
+    }
+
+    #[test]
+    fn keeps_other_conditions_as_filters() {
+        let plan = plan_last("Edge(1, 2).\n?- Edge(x, y), 1 == x, y > 1, x == y.\n");
+        assert_eq!(plan.head.name, "query#1");
+        assert_eq!(constants(&plan.scan.atom), vec![(0, Value::Integer(1))]);
+        assert_eq!(plan.steps.len(), 2);
+        assert!(plan.steps.iter().all(|step| matches!(step, Step::Filter(_))));
+    }
+
+    #[test]
+    fn answers_queries_with_a_tuple_per_binding() {
+        let (_, _, engine) = compile_program(
+            "Edge(1, 2). Edge(2, 3). Edge(3, 4).\n\
+             ?- Edge(x, y), y > 2.\n?- Edge(2, 3).\n?- Edge(3, 2).\n?- Edge(x, _), x == 3.\n",
+            Semantics::Stratified,
+        );
+        assert_eq!(tuples(&engine, "query#1"), vec!["2, 3", "3, 4"]);
+        assert_eq!(tuples(&engine, "query#2"), vec![""]);
+        assert_eq!(tuples(&engine, "query#3"), Vec::<String>::new());
+        assert_eq!(tuples(&engine, "query#4"), vec!["3"]);
+    }
+
+    #[test]
+    fn answers_to_queries_can_be_undefined() {
+        let (program, _, engine) = compile_program(
+            "Move(1, 2). Move(2, 1). Move(3, 4).\n.iterate {\n  Win(x) :- Move(x, y), !Win(y).\n}\n?- Win(x).\n",
+            Semantics::WellFounded,
+        );
+        let relation = query_relation(0, program.queries().next().unwrap());
+        assert_eq!(tuples(&engine, &relation.name), vec!["3"]);
+        assert_eq!(rows(engine.undefined_tuples(&relation).unwrap().unwrap()), vec!["1", "2"]);
+    }
+}
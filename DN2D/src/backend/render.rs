@@ -0,0 +1,143 @@
+use differential_dataflow::{lattice::Lattice, operators::{Join, Reduce, Threshold}, Collection};
+use timely::dataflow::Scope;
+
+use crate::{
+    ast::Identifier,
+    backend::{aggregate::AggregatePlan, plan::{HeadTerm, RulePlan, Step}, Tuple, Value},
+    semantic::Type,
+};
+
+fn project(tuple: &[Value], columns: &[usize]) -> Tuple {
+    columns.iter().map(|column| tuple[*column].clone()).collect()
+}
+
+/// Instantiates a rule in scope `G`, reading the relations of its positive atoms
+/// through `positive` and those of its negated atoms through `negative`.
+pub fn render_rule<G, P, N>(plan: &RulePlan, mut positive: P, mut negative: N) -> Collection<G, Tuple>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+    P: FnMut(&Identifier) -> Collection<G, Tuple>,
+    N: FnMut(&Identifier) -> Collection<G, Tuple>,
+{
+    let atom = plan.scan.atom.clone();
+    let columns = plan.scan.columns.clone();
+    let mut bound = positive(&atom.relation)
+        .filter(move |tuple| atom.matches(tuple))
+        .map(move |tuple| project(&tuple, &columns));
+
+    for step in &plan.steps {
+        bound = match step {
+            Step::Join { atom, bound_keys, atom_keys, new_columns } => {
+                let (atom, atom_keys, new_columns) = (atom.clone(), atom_keys.clone(), new_columns.clone());
+                let right = positive(&atom.relation)
+                    .filter(move |tuple| atom.matches(tuple))
+                    .map(move |tuple| (project(&tuple, &atom_keys), project(&tuple, &new_columns)));
+
+                let bound_keys = bound_keys.clone();
+                bound
+                    .map(move |row| (project(&row, &bound_keys), row))
+                    .join_map(&right, |_key, row, new| {
+                        let mut row = row.clone();
+                        row.extend(new.iter().cloned());
+                        row
+                    })
+            }
+            Step::Antijoin { atom, bound_keys, atom_keys } => {
+                let (atom, atom_keys) = (atom.clone(), atom_keys.clone());
+                let right = negative(&atom.relation)
+                    .filter(move |tuple| atom.matches(tuple))
+                    .map(move |tuple| project(&tuple, &atom_keys))
+                    .distinct();
+
+                let bound_keys = bound_keys.clone();
+                bound
+                    .map(move |row| (project(&row, &bound_keys), row))
+                    .antijoin(&right)
+                    .map(|(_key, row)| row)
+            }
+            Step::Filter(condition) => {
+                let condition = condition.clone();
+                bound.filter(move |row| condition.is_true(row))
+            }
+        };
+    }
+
+    let projection = plan.projection.clone();
+    let derived = if plan.is_aggregation() {
+        aggregate(&bound, projection)
+    } else {
+        bound.flat_map(move |row| {
+            projection.iter()
+                .map(|term| match term {
+                    HeadTerm::Scalar(expr) => expr.eval(&row),
+                    HeadTerm::Aggregate(_) => unreachable!(),
+                })
+                .collect::<Option<Tuple>>()
+        })
+    };
+
+    // Integers derived for a float column are widened, so that they equal the
+    // floats that the column's other values, read or derived, already are.
+    let types = plan.types.clone();
+    if !types.contains(&Some(Type::Float)) {
+        return derived;
+    }
+    derived.flat_map(move |tuple| {
+        tuple.into_iter()
+            .zip(&types)
+            .map(|(value, ty)| match ty {
+                Some(ty) => value.conform(*ty),
+                None => Some(value),
+            })
+            .collect::<Option<Tuple>>()
+    })
+}
+
+/// Groups the distinct bindings of a rule body by the head's scalar terms and
+/// computes the head's aggregates over each group.
+fn aggregate<G>(bound: &Collection<G, Tuple>, projection: Vec<HeadTerm>) -> Collection<G, Tuple>
+where
+    G: Scope,
+    G::Timestamp: Lattice + Ord,
+{
+    let terms = projection.clone();
+    let aggregates: Vec<AggregatePlan> = projection.iter()
+        .filter_map(|term| match term {
+            HeadTerm::Aggregate(aggregate) => Some(aggregate.clone()),
+            HeadTerm::Scalar(_) => None,
+        })
+        .collect();
+
+    bound
+        .distinct()
+        .flat_map(move |row| {
+            let mut key = Vec::new();
+            let mut args = Vec::new();
+            for term in &terms {
+                match term {
+                    HeadTerm::Scalar(expr) => key.push(expr.eval(&row)?),
+                    HeadTerm::Aggregate(aggregate) => args.push(aggregate.arg.eval(&row)?),
+                }
+            }
+            Some((key, args))
+        })
+        .reduce(move |_key, input, output| {
+            let values = aggregates.iter()
+                .enumerate()
+                .map(|(index, aggregate)| aggregate.apply(input.iter().map(|(args, diff)| (&args[index], *diff))))
+                .collect::<Option<Tuple>>();
+            if let Some(values) = values {
+                output.push((values, 1));
+            }
+        })
+        .map(move |(key, values)| {
+            let (mut key, mut values) = (key.into_iter(), values.into_iter());
+            projection.iter()
+                .map(|term| match term {
+                    HeadTerm::Scalar(_) => key.next().unwrap(),
+                    HeadTerm::Aggregate(_) => values.next().unwrap(),
+                })
+                .collect()
+        })
+}
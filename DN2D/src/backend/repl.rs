@@ -0,0 +1,342 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    ast::{Atom, Expression, Identifier, Parsable, Parser, Program},
+    backend::{expr::compare, plan::fold_constant, runner::Runner, Change, Engine, EngineError, ProgramPlan, Tuple, Value},
+    io::FormatRegistry,
+    lexer::{Lexer, TokenKind},
+    semantic::SymbolTable,
+};
+
+const HELP: &str = "\
+Commands:
+  +Relation(1, \"a\").    Insert a tuple, once committed
+  -Relation(1, \"a\").    Retract a tuple, once committed
+  ?Relation(1, x, _).   List the tuples that match, as of the last commit
+  commit                Evaluate the pending changes and print the changes they cause
+  .dump Relation        List every tuple of a relation
+  help                  Show this message
+  quit                  Leave the REPL";
+
+/// Loads `program` into `engine` like [`run`](crate::backend::run), without
+/// writing its outputs, then reads commands from standard input to update and
+/// query the live dataflow until the input ends or `quit` is entered.
+pub fn repl(
+    program: &Program,
+    mut engine: Engine,
+    symbols: &SymbolTable,
+    formats: &FormatRegistry,
+    base_dir: &Path,
+) -> Result<(), EngineError> {
+    let runner = Runner { program, symbols, formats, base_dir };
+
+    // What has been inserted into the input of each relation, so that only
+    // tuples that are there can be retracted.
+    let mut inputs: HashMap<Identifier, BTreeMap<Tuple, isize>> = HashMap::new();
+    for (relation, tuple) in ProgramPlan::new(program, symbols)?.facts {
+        *inputs.entry(relation).or_default().entry(tuple).or_default() += 1;
+    }
+
+    for relation in symbols.relations.keys() {
+        engine.track_changes(relation)?;
+    }
+    for read in runner.tuple_reads() {
+        for tuple in runner.read(read)? {
+            *inputs.entry(read.name.clone()).or_default().entry(tuple.clone()).or_default() += 1;
+            engine.insert(&read.name, tuple)?;
+        }
+    }
+    for (relation, change) in runner.read_changes(&mut engine)? {
+        *inputs.entry(relation.clone()).or_default().entry(change.tuple).or_default() += change.diff;
+    }
+    engine.commit();
+    for relation in symbols.relations.keys() {
+        engine.take_changes(relation)?;
+        engine.take_undefined_changes(relation)?;
+    }
+
+    let mut session = Session { engine, symbols, inputs, pending: 0 };
+    println!("Loaded {} relations. Enter 'help' for the list of commands.", symbols.relations.len());
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush().map_err(|err| EngineError::io(err.to_string()))?;
+
+        let Some(line) = lines.next() else { break };
+        let line = line.map_err(|err| EngineError::io(format!("Could not read a command: {}", err)))?;
+
+        match line.trim() {
+            "" => {}
+            "quit" | "exit" => break,
+            "help" => println!("{}", HELP),
+            command => {
+                if let Err(message) = session.execute(command) {
+                    eprintln!("{}", message);
+                }
+            }
+        }
+    }
+
+    if session.pending > 0 {
+        println!("Discarded {} uncommitted changes.", session.pending);
+    }
+    Ok(())
+}
+
+struct Session<'a> {
+    engine: Engine,
+    symbols: &'a SymbolTable,
+    inputs: HashMap<Identifier, BTreeMap<Tuple, isize>>,
+    /// The number of changes made since the last commit.
+    pending: usize,
+}
+
+impl Session<'_> {
+    fn execute(&mut self, command: &str) -> Result<(), String> {
+        if command == "commit" {
+            return self.commit().map_err(|err| err.to_string());
+        }
+        if let Some(name) = command.strip_prefix(".dump") {
+            return self.dump(name.trim());
+        }
+
+        let mut chars = command.chars();
+        match chars.next() {
+            Some('+') => self.update(&parse_atom(chars.as_str())?, 1),
+            Some('-') => self.update(&parse_atom(chars.as_str())?, -1),
+            Some('?') => self.query(&parse_atom(chars.as_str())?),
+            _ => Err(format!("Unknown command '{}', enter 'help' for the list of commands", command)),
+        }
+    }
+
+    /// Stages the insertion (`diff` 1) or retraction (`diff` -1) of a fact.
+    fn update(&mut self, atom: &Atom, diff: isize) -> Result<(), String> {
+        let relation = &self.relation(&atom.name, atom.terms.len())?.clone();
+
+        let tuple = atom.terms.iter()
+            .enumerate()
+            .map(|(column, term)| {
+                let value = fold_constant(term)
+                    .ok_or_else(|| format!("Column {} of a fact must be a constant", column + 1))?;
+                match self.symbols.relations[relation].types[column] {
+                    Some(ty) => value.clone().conform(ty).ok_or_else(|| format!(
+                        "Column {} of '{}' has type {} but found {}", column + 1, relation, ty, value.to_source()
+                    )),
+                    None => Ok(value),
+                }
+            })
+            .collect::<Result<Tuple, String>>()?;
+
+        let count = self.inputs.entry(relation.clone()).or_default().entry(tuple.clone()).or_default();
+        if diff < 0 && *count <= 0 {
+            return Err(format!(
+                "{} cannot be retracted, as it is not a fact, an input or an inserted tuple",
+                format_atom(relation, &tuple)
+            ));
+        }
+        *count += diff;
+
+        self.engine.update(relation, tuple, diff).map_err(|err| err.to_string())?;
+        self.pending += 1;
+        Ok(())
+    }
+
+    /// Evaluates the staged changes and prints the changes to every relation.
+    fn commit(&mut self) -> Result<(), EngineError> {
+        let epoch = self.engine.epoch();
+        self.engine.commit();
+
+        let mut count = 0;
+        for relation in self.symbols.relations.keys() {
+            count += print_changes(relation, &self.engine.take_changes(relation)?, "");
+            if let Some(undefined) = self.engine.take_undefined_changes(relation)? {
+                count += print_changes(relation, &undefined, " (undefined)");
+            }
+        }
+
+        println!("Committed epoch {}: {} changes in, {} changes out.", epoch, self.pending, count);
+        self.pending = 0;
+        Ok(())
+    }
+
+    /// Prints the tuples of a relation that match the constants and repeated
+    /// variables of `atom`.
+    fn query(&self, atom: &Atom) -> Result<(), String> {
+        let relation = self.relation(&atom.name, atom.terms.len())?;
+
+        let mut pattern = Vec::new();
+        for term in &atom.terms {
+            pattern.push(match term {
+                Expression::Wildcard(_) => Term::Any,
+                Expression::Variable(variable) => Term::Variable(variable),
+                term => Term::Constant(fold_constant(term)
+                    .ok_or("The terms of a query must be constants, variables or '_'")?),
+            });
+        }
+
+        let tuples = self.engine.tuples(relation).map_err(|err| err.to_string())?;
+        let undefined = self.engine.undefined_tuples(relation).map_err(|err| err.to_string())?;
+
+        let mut count = 0;
+        for (tuples, suffix) in [(tuples, ""), (undefined.unwrap_or_default(), " (undefined)")] {
+            for tuple in tuples.iter().filter(|tuple| matches(&pattern, tuple)) {
+                println!("{}.{}", format_atom(relation, tuple), suffix);
+                count += 1;
+            }
+        }
+        println!("{} matching tuples.", count);
+        Ok(())
+    }
+
+    fn dump(&self, name: &str) -> Result<(), String> {
+        let (relation, schema) = self.symbols.relations.iter()
+            .find(|(relation, _)| relation.name == name)
+            .ok_or_else(|| format!("Unknown relation '{}'", name))?;
+        let terms = (0..schema.arity).map(|_| Expression::Wildcard(relation.span)).collect();
+        self.query(&Atom { name: relation.clone(), terms, span: relation.span })
+    }
+
+    /// The relation named by an atom of the given arity.
+    fn relation(&self, name: &Identifier, arity: usize) -> Result<&Identifier, String> {
+        let (relation, schema) = self.symbols.relations.get_key_value(name)
+            .ok_or_else(|| format!("Unknown relation '{}'", name))?;
+        if schema.arity != arity {
+            return Err(format!("Relation '{}' has {} columns but {} are given", name, schema.arity, arity));
+        }
+        Ok(relation)
+    }
+}
+
+/// A term of a query atom.
+enum Term<'a> {
+    Any,
+    Constant(Value),
+    Variable(&'a Identifier),
+}
+
+fn matches(pattern: &[Term], tuple: &Tuple) -> bool {
+    let mut bound: HashMap<&Identifier, &Value> = HashMap::new();
+    pattern.iter().zip(tuple).all(|(term, value)| match term {
+        Term::Any => true,
+        Term::Constant(constant) => compare(constant, value) == Some(Ordering::Equal),
+        Term::Variable(variable) => match bound.get(variable) {
+            Some(earlier) => compare(earlier, value) == Some(Ordering::Equal),
+            None => {
+                bound.insert(variable, value);
+                true
+            }
+        },
+    })
+}
+
+/// Parses an atom, optionally followed by a `.`, from a command.
+fn parse_atom(source: &str) -> Result<Atom, String> {
+    let tokens = Lexer::new(source)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.to_string())?;
+
+    let mut parser = Parser::new(source, tokens);
+    let atom = Atom::parse(&mut parser).map_err(|err| err.to_string())?;
+    if parser.peek_is(&TokenKind::Dot).map_err(|err| err.to_string())? {
+        parser.consume();
+    }
+    if let Some(token) = parser.consume() {
+        return Err(parser.unexpected_token_error(&token, "the end of the command").to_string());
+    }
+    Ok(atom)
+}
+
+fn print_changes(relation: &Identifier, changes: &[Change], suffix: &str) -> usize {
+    for change in changes {
+        let sign = if change.diff > 0 { '+' } else { '-' };
+        println!("{}{}.{}", sign, format_atom(relation, &change.tuple), suffix);
+    }
+    changes.len()
+}
+
+fn format_atom(relation: &Identifier, tuple: &Tuple) -> String {
+    let terms: Vec<String> = tuple.iter().map(Value::to_source).collect();
+    format!("{}({})", relation, terms.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{engine::tests::{compile_program, relation, tuples}, value::Float, Semantics};
+
+    const SOURCE: &str = ".decl Edge(a: int, b: float).\nEdge(1, 2.0).\nReach(x) :- Edge(x, _).\n";
+
+    fn session<'a>(symbols: &'a SymbolTable, engine: Engine, program: &Program) -> Session<'a> {
+        let mut inputs: HashMap<Identifier, BTreeMap<Tuple, isize>> = HashMap::new();
+        for (relation, tuple) in ProgramPlan::new(program, symbols).unwrap().facts {
+            *inputs.entry(relation).or_default().entry(tuple).or_default() += 1;
+        }
+        Session { engine, symbols, inputs, pending: 0 }
+    }
+
+    #[test]
+    fn parses_an_atom_with_an_optional_dot() {
+        let atom = parse_atom(" Edge(1, \"a\").").unwrap();
+        assert_eq!(atom.name.name, "Edge");
+        assert_eq!(atom.terms.len(), 2);
+        assert!(parse_atom("Edge(1, _)").is_ok());
+
+        assert!(parse_atom("Edge(1). Edge(2).").unwrap_err().contains("expected the end of the command"));
+        assert!(parse_atom("Edge(1").unwrap_err().contains("found the end of the file"));
+        assert!(parse_atom("Edge(1) @").is_err());
+    }
+
+    #[test]
+    fn matches_constants_and_repeated_variables() {
+        let x = relation("x");
+        let pattern = [Term::Constant(Value::Integer(1)), Term::Variable(&x), Term::Variable(&x), Term::Any];
+        let tuple = |values: [i64; 4]| values.into_iter().map(Value::Integer).collect::<Tuple>();
+
+        assert!(matches(&pattern, &tuple([1, 2, 2, 3])));
+        assert!(!matches(&pattern, &tuple([1, 2, 3, 3])));
+        assert!(!matches(&pattern, &tuple([2, 2, 2, 3])));
+        assert!(matches(&[Term::Constant(Value::Float(Float(1.0)))], &vec![Value::Integer(1)]));
+    }
+
+    #[test]
+    fn executes_updates_once_committed() {
+        let (program, symbols, engine) = compile_program(SOURCE, Semantics::Stratified);
+        let mut session = session(&symbols, engine, &program);
+
+        session.execute("+Edge(2, 3).").unwrap();
+        session.execute("-Edge(1, 2)").unwrap();
+        assert_eq!(session.pending, 2);
+        assert_eq!(tuples(&session.engine, "Reach"), vec!["1"]);
+
+        session.execute("commit").unwrap();
+        assert_eq!(session.pending, 0);
+        assert_eq!(tuples(&session.engine, "Edge"), vec!["2, 3.0"]);
+        assert_eq!(tuples(&session.engine, "Reach"), vec!["2"]);
+    }
+
+    #[test]
+    fn rejects_malformed_commands() {
+        let (program, symbols, engine) = compile_program(SOURCE, Semantics::Stratified);
+        let mut session = session(&symbols, engine, &program);
+
+        let error = |session: &mut Session, command| session.execute(command).unwrap_err();
+        assert_eq!(error(&mut session, "Edge(1, 2)"), "Unknown command 'Edge(1, 2)', enter 'help' for the list of commands");
+        assert_eq!(error(&mut session, "+Path(1, 2)"), "Unknown relation 'Path'");
+        assert_eq!(error(&mut session, "+Edge(1)"), "Relation 'Edge' has 2 columns but 1 are given");
+        assert_eq!(error(&mut session, "+Edge(x, 2)"), "Column 1 of a fact must be a constant");
+        assert_eq!(error(&mut session, "+Edge(\"a\", 2)"), "Column 1 of 'Edge' has type integer but found \"a\"");
+        assert_eq!(
+            error(&mut session, "-Edge(5, 6)"),
+            "Edge(5, 6.0) cannot be retracted, as it is not a fact, an input or an inserted tuple"
+        );
+        assert_eq!(error(&mut session, ".dump Path"), "Unknown relation 'Path'");
+        assert_eq!(session.pending, 0);
+    }
+}
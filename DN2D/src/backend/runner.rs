@@ -0,0 +1,386 @@
+use std::{collections::{btree_map, BTreeMap}, fmt, fs::OpenOptions, io::{self, BufWriter, Write}, path::{Path, PathBuf}};
+
+use crate::{
+    ast::{Identifier, Program, ReadDirective, Statement, WriteDirective},
+    backend::{plan::query_relation, Change, Engine, EngineError, Tuple},
+    io::{FormatRegistry, IoError},
+    semantic::SymbolTable,
+};
+
+/// Evaluates `program` once on `engine`: loads every `.read` input, runs the
+/// dataflow to completion and writes every `.write` output. Relative paths are
+/// resolved against `base_dir`, the directory of the program file.
+///
+/// A `.write` to `"io::stdout"` or `"io::stderr"` goes to that stream instead
+/// of a file. Under the well-founded semantics the undefined tuples of each
+/// written relation go to a sibling file, e.g. `Win.undefined.csv` next to
+/// `Win.csv`, or after the relation on the same stream.
+pub fn run(
+    program: &Program,
+    mut engine: Engine,
+    symbols: &SymbolTable,
+    formats: &FormatRegistry,
+    base_dir: &Path,
+) -> Result<(), EngineError> {
+    let runner = Runner { program, symbols, formats, base_dir };
+
+    runner.track_changes(&mut engine, false)?;
+
+    for read in runner.tuple_reads() {
+        for tuple in runner.read(read)? {
+            engine.insert(&read.name, tuple)?;
+        }
+    }
+    runner.read_changes(&mut engine)?;
+
+    engine.commit();
+    runner.write(&engine, false)?;
+    runner.answer(&engine)
+}
+
+/// What is needed to load the inputs and store the outputs of a program.
+pub struct Runner<'a> {
+    pub program: &'a Program,
+    pub symbols: &'a SymbolTable,
+    pub formats: &'a FormatRegistry,
+    pub base_dir: &'a Path,
+}
+
+impl<'a> Runner<'a> {
+    pub fn reads(&self) -> impl Iterator<Item = &'a ReadDirective> {
+        self.program.statements.iter().filter_map(|statement| match statement {
+            Statement::Read(read) => Some(read),
+            _ => None,
+        })
+    }
+
+    /// The `.read` directives whose format reads tuples rather than changes.
+    pub fn tuple_reads(&self) -> impl Iterator<Item = &'a ReadDirective> + '_ {
+        self.reads().filter(|read| self.formats.reader(&read.format).is_some())
+    }
+
+    pub fn writes(&self) -> impl Iterator<Item = &'a WriteDirective> {
+        self.program.statements.iter().filter_map(|statement| match statement {
+            Statement::Write(write) => Some(write),
+            _ => None,
+        })
+    }
+
+    /// The file a `.read` directive loads.
+    pub fn path(&self, read: &ReadDirective) -> PathBuf {
+        resolve(self.base_dir, &read.path)
+    }
+
+    /// Loads the tuples of a `.read` directive.
+    pub fn read(&self, read: &ReadDirective) -> Result<Vec<Tuple>, EngineError> {
+        let reader = self.formats.reader(&read.format)
+            .ok_or_else(|| EngineError::new(format!("Unsupported input format '{}'", read.format)))?;
+        Ok(reader.read(&self.path(read), &self.symbols.relations[&read.name])?)
+    }
+
+    /// Feeds the changes of every `.read` directive in a change format into
+    /// `engine` in epoch order, advancing the engine to the epoch of each
+    /// change that is later than its current one. Returns the changes fed.
+    pub fn read_changes(&self, engine: &mut Engine) -> Result<Vec<(&'a Identifier, Change)>, EngineError> {
+        let mut changes = Vec::new();
+        for read in self.reads() {
+            let Some(reader) = self.formats.change_reader(&read.format) else { continue };
+            if self.formats.reader(&read.format).is_some() {
+                continue;
+            }
+            for change in reader.read_changes(&self.path(read), &self.symbols.relations[&read.name])? {
+                changes.push((&read.name, change));
+            }
+        }
+
+        changes.sort_by_key(|(_, change)| change.epoch);
+        for (relation, change) in &changes {
+            if change.epoch > engine.epoch() {
+                engine.advance_to(change.epoch);
+            }
+            engine.update(relation, change.tuple.clone(), change.diff)?;
+        }
+        Ok(changes)
+    }
+
+    /// Has `engine` record the changes of every relation written in a change
+    /// format, and with `snapshots` also of every relation written as a
+    /// snapshot, so that [`Runner::write`] can tell whether it changed.
+    pub fn track_changes(&self, engine: &mut Engine, snapshots: bool) -> Result<(), EngineError> {
+        for write in self.writes() {
+            if snapshots || (self.formats.writer(&write.format).is_none() && self.formats.change_writer(&write.format).is_some()) {
+                engine.track_changes(&write.name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every `.write` output of the last commit of `engine`. Snapshots
+    /// replace the contents of their file and changes are written to it. With
+    /// `update`, changes are appended instead, and only the outputs whose
+    /// tuples changed in the last commit are written. What was written is
+    /// reported on stderr, so that it does not mix with the tuples written to
+    /// `io::stdout`.
+    pub fn write(&self, engine: &Engine, update: bool) -> Result<(), EngineError> {
+        // Taken once per relation, as a relation may be written more than once.
+        let mut changes = BTreeMap::new();
+        for write in self.writes() {
+            if let btree_map::Entry::Vacant(entry) = changes.entry(&write.name) {
+                entry.insert((engine.take_changes(&write.name)?, engine.take_undefined_changes(&write.name)?));
+            }
+        }
+        let unchanged = |changes: &[Change]| update && changes.is_empty();
+
+        for write in self.writes() {
+            let schema = &self.symbols.relations[&write.name];
+            let sink = Sink::new(self.base_dir, &write.path);
+            let (changes, undefined_changes) = &changes[&write.name];
+
+            if let Some(writer) = self.formats.writer(&write.format) {
+                if !unchanged(changes) {
+                    let tuples = engine.tuples(&write.name)?;
+                    sink.write(false, |out| writer.write(out, schema, &tuples))?;
+
+                    eprintln!("Wrote {} of '{}' to '{}'.", counted(tuples.len(), "tuple"), write.name, sink);
+                }
+
+                if let Some(undefined) = engine.undefined_tuples(&write.name)? {
+                    if undefined_changes.as_deref().is_some_and(unchanged) {
+                        continue;
+                    }
+                    let sink = sink.undefined();
+                    sink.write(false, |out| {
+                        sink.introduce(out, &format!("undefined tuples of '{}'", write.name))?;
+                        writer.write(out, schema, &undefined)
+                    })?;
+
+                    eprintln!("Wrote {} of '{}' to '{}'.", counted(undefined.len(), "undefined tuple"), write.name, sink);
+                }
+            } else if let Some(writer) = self.formats.change_writer(&write.format) {
+                if !unchanged(changes) {
+                    sink.write(update, |out| writer.write_changes(out, schema, changes))?;
+
+                    eprintln!("Wrote {} of '{}' to '{}'.", counted(changes.len(), "change"), write.name, sink);
+                }
+
+                if let Some(undefined) = undefined_changes {
+                    if unchanged(undefined) {
+                        continue;
+                    }
+                    let sink = sink.undefined();
+                    sink.write(update, |out| {
+                        sink.introduce(out, &format!("undefined changes of '{}'", write.name))?;
+                        writer.write_changes(out, schema, undefined)
+                    })?;
+
+                    eprintln!("Wrote {} of '{}' to '{}'.", counted(undefined.len(), "undefined change"), write.name, sink);
+                }
+            } else {
+                return Err(EngineError::new(format!("Unsupported output format '{}'", write.format)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints the answers to every query of the program as of the last commit
+    /// of `engine`, one line of variable bindings per answer.
+    pub fn answer(&self, engine: &Engine) -> Result<(), EngineError> {
+        for (index, query) in self.program.queries().enumerate() {
+            let relation = query_relation(index, query);
+            let variables = query.variables();
+            let answers = engine.tuples(&relation)?;
+            let undefined = engine.undefined_tuples(&relation)?.unwrap_or_default();
+
+            println!("Answers to the query at line {}:", query.span.start_line);
+            if variables.is_empty() {
+                let answer = match (answers.is_empty(), undefined.is_empty()) {
+                    (false, _) => "true.",
+                    (true, false) => "undefined.",
+                    (true, true) => "false.",
+                };
+                println!("{}", answer);
+                continue;
+            }
+
+            if answers.is_empty() && undefined.is_empty() {
+                println!("No answers.");
+            }
+            for (tuples, suffix) in [(answers, ""), (undefined, " (undefined)")] {
+                for tuple in tuples {
+                    let bindings: Vec<String> = variables.iter()
+                        .zip(&tuple)
+                        .map(|(variable, value)| format!("{} = {}", variable, value.to_source()))
+                        .collect();
+                    println!("{}{}", bindings.join(", "), suffix);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a `.write` directive sends its tuples.
+enum Sink {
+    Stdout,
+    Stderr,
+    File(PathBuf),
+}
+
+impl Sink {
+    fn new(base_dir: &Path, path: &str) -> Sink {
+        match path {
+            "io::stdout" => Sink::Stdout,
+            "io::stderr" => Sink::Stderr,
+            path => Sink::File(resolve(base_dir, path)),
+        }
+    }
+
+    /// Where the undefined tuples of the relation written here go: a file of
+    /// their own, or the same stream after the true tuples.
+    fn undefined(&self) -> Sink {
+        match self {
+            Sink::Stdout => Sink::Stdout,
+            Sink::Stderr => Sink::Stderr,
+            Sink::File(path) => Sink::File(undefined_path(path)),
+        }
+    }
+
+    /// Heads a section of a stream with a line naming what follows, so that
+    /// the undefined tuples can be told from the true ones before them. Files
+    /// hold a single section and get no heading.
+    fn introduce(&self, out: &mut dyn Write, section: &str) -> io::Result<()> {
+        match self {
+            Sink::Stdout | Sink::Stderr => writeln!(out, "--- {} ---", section),
+            Sink::File(_) => Ok(()),
+        }
+    }
+
+    /// Writes to the sink, after the current contents of its file if `append`
+    /// is set.
+    fn write(&self, append: bool, write: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> Result<(), IoError> {
+        let error = |err: io::Error| IoError::new(Path::new(&self.to_string()), format!("Could not write: {}", err));
+
+        let mut out: Box<dyn Write> = match self {
+            Sink::Stdout => Box::new(io::stdout().lock()),
+            Sink::Stderr => Box::new(io::stderr().lock()),
+            Sink::File(path) => {
+                let file = OpenOptions::new().write(true).create(true).append(append).truncate(!append).open(path);
+                Box::new(BufWriter::new(file.map_err(error)?))
+            }
+        };
+        write(&mut out).map_err(error)?;
+        out.flush().map_err(error)
+    }
+}
+
+impl fmt::Display for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sink::Stdout => write!(f, "io::stdout"),
+            Sink::Stderr => write!(f, "io::stderr"),
+            Sink::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+pub fn resolve(base_dir: &Path, path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        base_dir.join(path)
+    }
+}
+
+fn undefined_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(extension) => path.with_file_name(format!("{}.undefined.{}", stem, extension.to_string_lossy())),
+        None => path.with_file_name(format!("{}.undefined", stem)),
+    }
+}
+
+/// `count` followed by `noun`, which is made plural unless `count` is one.
+fn counted(count: usize, noun: &str) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {}{}", count, noun, plural)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::{
+        backend::{engine::tests::{compile, compile_program, relation}, Semantics, Value},
+        semantic::{RelationSchema, Type},
+    };
+
+    /// What `Runner::write` sends to `sink` for the undefined tuples of `Win`.
+    fn undefined_section(sink: &Sink) -> String {
+        let engine = compile(
+            "Move(1, 2). Move(2, 1).\n.iterate {\n  Win(x) :- Move(x, y), !Win(y).\n}\n",
+            Semantics::WellFounded,
+        );
+        let schema = RelationSchema {
+            name: relation("Win"),
+            arity: 1,
+            columns: vec!["x".to_string()],
+            types: vec![Some(Type::Integer)],
+            declared: false,
+        };
+        let undefined = engine.undefined_tuples(&schema.name).unwrap().unwrap();
+        let formats = FormatRegistry::default();
+        let writer = formats.writer("csv").unwrap();
+
+        let mut out = Vec::new();
+        sink.introduce(&mut out, "undefined tuples of 'Win'").unwrap();
+        writer.write(&mut out, &schema, &undefined).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn undefined_tuples_follow_a_marker_line_on_streams() {
+        assert_eq!(undefined_section(&Sink::Stdout), "--- undefined tuples of 'Win' ---\n1\n2\n");
+        assert_eq!(undefined_section(&Sink::File(PathBuf::from("Win.csv"))), "1\n2\n");
+    }
+
+    #[test]
+    fn undefined_tuples_of_a_file_go_to_a_sibling_file() {
+        assert_eq!(undefined_path(Path::new("out/Win.csv")), Path::new("out/Win.undefined.csv"));
+        assert_eq!(undefined_path(Path::new("Win")), Path::new("Win.undefined"));
+    }
+
+    #[test]
+    fn counts_of_one_are_singular() {
+        assert_eq!(counted(0, "tuple"), "0 tuples");
+        assert_eq!(counted(1, "undefined tuple"), "1 undefined tuple");
+        assert_eq!(counted(2, "change"), "2 changes");
+    }
+
+    #[test]
+    fn updates_rewrite_only_the_snapshots_that_changed() {
+        let source = "A(1). B(1).\n.write A to \"A.csv\" as \"csv\".\n.write B to \"B.csv\" as \"csv\".\n";
+        let (program, symbols, mut engine) = compile_program(source, Semantics::Stratified);
+        let formats = FormatRegistry::default();
+        let base_dir = std::env::temp_dir().join(format!("dn2d-runner-{}", std::process::id()));
+        fs::create_dir_all(&base_dir).unwrap();
+        let runner = Runner { program: &program, symbols: &symbols, formats: &formats, base_dir: &base_dir };
+        runner.track_changes(&mut engine, true).unwrap();
+
+        engine.update(&relation("A"), vec![Value::Integer(2)], 1).unwrap();
+        engine.commit();
+        runner.write(&engine, false).unwrap();
+        fs::remove_file(base_dir.join("A.csv")).unwrap();
+        fs::remove_file(base_dir.join("B.csv")).unwrap();
+
+        engine.update(&relation("A"), vec![Value::Integer(3)], 1).unwrap();
+        engine.commit();
+        runner.write(&engine, true).unwrap();
+        let written = (fs::read_to_string(base_dir.join("A.csv")), base_dir.join("B.csv").exists());
+        fs::remove_dir_all(&base_dir).unwrap();
+
+        assert_eq!(written.0.unwrap(), "1\n2\n3\n");
+        assert!(!written.1);
+    }
+}
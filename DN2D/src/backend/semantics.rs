@@ -0,0 +1,33 @@
+use std::{fmt, str::FromStr};
+
+/// How rules with negation are given a meaning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Semantics {
+    /// Negated relations must be fully computed before they are negated, so
+    /// programs with negation through recursion are rejected.
+    Stratified,
+    /// Every program has a meaning, though some tuples may be neither true
+    /// nor false.
+    WellFounded,
+}
+
+impl FromStr for Semantics {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "stratified" => Ok(Semantics::Stratified),
+            "well-founded" => Ok(Semantics::WellFounded),
+            other => Err(format!("Unknown semantics '{}', expected 'stratified' or 'well-founded'", other)),
+        }
+    }
+}
+
+impl fmt::Display for Semantics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Semantics::Stratified => write!(f, "stratified"),
+            Semantics::WellFounded => write!(f, "well-founded"),
+        }
+    }
+}
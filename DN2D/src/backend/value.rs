@@ -0,0 +1,137 @@
+use std::{cmp::Ordering, fmt, hash::{Hash, Hasher}, io::Write};
+
+use abomonation::Abomonation;
+
+use crate::{ast::Constant, semantic::Type};
+
+/// A row of a relation, as it flows through the dataflow.
+pub type Tuple = Vec<Value>;
+
+/// `f64` with a total order, so that it can be used as differential data.
+#[derive(Debug, Clone, Copy)]
+pub struct Float(pub f64);
+
+impl Abomonation for Float {}
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Float {}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_finite() && self.0.fract() == 0.0 {
+            write!(f, "{:.1}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+/// A runtime value of a relation column.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Value {
+    Boolean(bool),
+    Integer(i64),
+    Float(Float),
+    String(String),
+}
+
+// Only the `String` variant owns heap data that needs to be serialized.
+impl Abomonation for Value {
+    unsafe fn entomb<W: Write>(&self, write: &mut W) -> std::io::Result<()> {
+        match self {
+            Value::String(s) => s.entomb(write),
+            _ => Ok(()),
+        }
+    }
+
+    fn extent(&self) -> usize {
+        match self {
+            Value::String(s) => s.extent(),
+            _ => 0,
+        }
+    }
+
+    unsafe fn exhume<'b>(&mut self, bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+        match self {
+            Value::String(s) => s.exhume(bytes),
+            _ => Some(bytes),
+        }
+    }
+}
+
+impl Value {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(f.0),
+            _ => None,
+        }
+    }
+
+    /// The value as it is written in a program, e.g. `"a"` for a string.
+    pub fn to_source(&self) -> String {
+        match self {
+            Value::String(s) => format!("\"{}\"", s),
+            value => value.to_string(),
+        }
+    }
+
+    /// The value as a value of type `ty`, widening integers for float columns,
+    /// or `None` if it does not have that type.
+    pub fn conform(self, ty: Type) -> Option<Value> {
+        match (ty, self) {
+            (Type::Float, Value::Integer(i)) => Some(Value::Float(Float(i as f64))),
+            (Type::Boolean, value @ Value::Boolean(_))
+            | (Type::Integer, value @ Value::Integer(_))
+            | (Type::Float | Type::Number, value @ Value::Float(_))
+            | (Type::Number, value @ Value::Integer(_))
+            | (Type::String, value @ Value::String(_)) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl From<&Constant> for Value {
+    fn from(constant: &Constant) -> Self {
+        match constant {
+            Constant::Integer(i) => Value::Integer(*i),
+            Constant::Float(f) => Value::Float(Float(*f)),
+            Constant::String(s) => Value::String(s.clone()),
+            Constant::Boolean(b) => Value::Boolean(*b),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::String(s) => write!(f, "{}", s),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::{
+    ast::{Identifier, Program, ReadDirective},
+    backend::{runner::Runner, Engine, EngineError, Tuple},
+    io::FormatRegistry,
+    semantic::SymbolTable,
+};
+
+/// How long to wait for further file events before re-evaluating, so that a
+/// file being saved in several writes is only read once.
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// A `.read` directive and the contents of its file as last loaded.
+struct Input<'a> {
+    read: &'a ReadDirective,
+    path: PathBuf,
+    contents: BTreeMap<Tuple, isize>,
+}
+
+/// Evaluates `program` like [`run`](crate::backend::run), then keeps the
+/// dataflow alive and watches the file of every `.read` directive whose
+/// format reads tuples. Inputs in a change format are only read once.
+///
+/// When files change, the difference between their old and new rows is fed
+/// into the engine as a new epoch, after which the snapshot outputs of the
+/// relations that changed are rewritten and the changes of that epoch are
+/// appended to change outputs. A file that cannot be read is reported and
+/// keeps its previous contents. Runs until the process is stopped.
+pub fn watch(
+    program: &Program,
+    mut engine: Engine,
+    symbols: &SymbolTable,
+    formats: &FormatRegistry,
+    base_dir: &Path,
+) -> Result<(), EngineError> {
+    let runner = Runner { program, symbols, formats, base_dir };
+
+    runner.track_changes(&mut engine, true)?;
+
+    let mut inputs = Vec::new();
+    for read in runner.tuple_reads() {
+        let contents = count(runner.read(read)?);
+        apply(&mut engine, &read.name, &BTreeMap::new(), &contents)?;
+        inputs.push(Input { read, path: watched_path(&runner.path(read)), contents });
+    }
+    runner.read_changes(&mut engine)?;
+
+    engine.commit();
+    runner.write(&engine, false)?;
+    runner.answer(&engine)?;
+
+    let error = |err: notify::Error| EngineError::io(format!("Could not watch the input files: {}", err));
+    let (sender, receiver) = channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(error)?;
+
+    // Directories are watched rather than files, as editors often save a file
+    // by replacing it.
+    let directories: BTreeSet<&Path> = inputs.iter().filter_map(|input| input.path.parent()).collect();
+    for directory in directories {
+        watcher.watch(directory, RecursiveMode::NonRecursive).map_err(error)?;
+    }
+    eprintln!("Watching {} input files for changes.", inputs.len());
+
+    loop {
+        let changed = next_changes(&receiver)?;
+
+        let mut updated = false;
+        for input in inputs.iter_mut().filter(|input| changed.contains(&input.path)) {
+            let contents = match runner.read(input.read) {
+                Ok(tuples) => count(tuples),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    continue;
+                }
+            };
+
+            let (inserted, retracted) = apply(&mut engine, &input.read.name, &input.contents, &contents)?;
+            if inserted + retracted > 0 {
+                eprintln!(
+                    "Applied {} insertions and {} retractions from '{}'.",
+                    inserted, retracted, input.path.display()
+                );
+                updated = true;
+            }
+            input.contents = contents;
+        }
+
+        if updated {
+            engine.commit();
+            runner.write(&engine, true)?;
+            runner.answer(&engine)?;
+        }
+    }
+}
+
+/// The paths touched by the next batch of file events, once no further
+/// events have arrived for [`SETTLE_TIME`].
+fn next_changes(receiver: &Receiver<notify::Result<Event>>) -> Result<BTreeSet<PathBuf>, EngineError> {
+    let stopped = || EngineError::io("Stopped receiving changes to the input files");
+
+    let mut changed = BTreeSet::new();
+    let mut event = receiver.recv().map_err(|_| stopped())?;
+    loop {
+        match event {
+            Ok(event) if !matches!(event.kind, EventKind::Access(_)) => changed.extend(event.paths),
+            Ok(_) => {}
+            Err(err) => eprintln!("Error while watching the input files: {}", err),
+        }
+
+        event = match receiver.recv_timeout(SETTLE_TIME) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) if !changed.is_empty() => return Ok(changed),
+            Err(RecvTimeoutError::Timeout) => receiver.recv().map_err(|_| stopped())?,
+            Err(RecvTimeoutError::Disconnected) => return Err(stopped()),
+        };
+    }
+}
+
+/// Feeds the difference between the `old` and `new` contents of a relation's
+/// input into `engine`, returning the number of insertions and retractions.
+fn apply(
+    engine: &mut Engine,
+    relation: &Identifier,
+    old: &BTreeMap<Tuple, isize>,
+    new: &BTreeMap<Tuple, isize>,
+) -> Result<(usize, usize), EngineError> {
+    let (mut inserted, mut retracted) = (0, 0);
+    for tuple in old.keys().chain(new.keys().filter(|tuple| !old.contains_key(*tuple))) {
+        let diff = new.get(tuple).copied().unwrap_or(0) - old.get(tuple).copied().unwrap_or(0);
+        if diff > 0 {
+            inserted += diff.unsigned_abs();
+        } else if diff < 0 {
+            retracted += diff.unsigned_abs();
+        } else {
+            continue;
+        }
+        engine.update(relation, tuple.clone(), diff)?;
+    }
+    Ok((inserted, retracted))
+}
+
+fn count(tuples: Vec<Tuple>) -> BTreeMap<Tuple, isize> {
+    let mut contents = BTreeMap::new();
+    for tuple in tuples {
+        *contents.entry(tuple).or_insert(0) += 1;
+    }
+    contents
+}
+
+/// The path under which file events report `path`: within the canonical
+/// form of its directory.
+fn watched_path(path: &Path) -> PathBuf {
+    let directory = path.parent().filter(|directory| !directory.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    match (fs::canonicalize(directory), path.file_name()) {
+        (Ok(directory), Some(name)) => directory.join(name),
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use differential_dataflow::{
+    lattice::Lattice,
+    operators::{iterate::Variable, Threshold},
+    Collection,
+};
+use timely::{dataflow::Scope, order::Product};
+
+use crate::{
+    ast::Identifier,
+    backend::{render::render_rule, ProgramPlan, Tuple},
+};
+
+/// Builds every relation of `plan` under the well-founded semantics, using the
+/// alternating fixpoint.
+///
+/// The relations defined by rules start out with no tuples known to be true.
+/// Negating those gives an overestimate of what is possibly true, and negating
+/// the overestimate in turn gives a larger underestimate of what is known to be
+/// true. Alternating until the underestimate stops growing leaves the true
+/// tuples, plus the undefined ones that are possibly but not known to be true.
+///
+/// Returns each relation with its true tuples and, for the relations defined by
+/// rules, its undefined tuples.
+#[allow(clippy::type_complexity)]
+pub fn build<G>(
+    scope: &mut G,
+    plan: &ProgramPlan,
+    inputs: &HashMap<Identifier, Collection<G, Tuple>>,
+) -> Vec<(Identifier, Collection<G, Tuple>, Option<Collection<G, Tuple>>)>
+where
+    G: Scope<Timestamp = u64>,
+{
+    let derived: BTreeSet<Identifier> = plan.rules.iter().map(|rule| rule.head.clone()).collect();
+    let base: HashMap<Identifier, Collection<G, Tuple>> = plan.relations.iter()
+        .filter(|relation| !derived.contains(*relation))
+        .map(|relation| (relation.clone(), inputs[relation].distinct()))
+        .collect();
+
+    let results = scope.iterative::<u32, _, _>(|outer| {
+        let entered_inputs: HashMap<_, _> = derived.iter()
+            .map(|name| (name.clone(), inputs[name].enter(outer)))
+            .collect();
+        let entered_base: HashMap<_, _> = base.iter()
+            .map(|(name, collection)| (name.clone(), collection.enter(outer)))
+            .collect();
+        let mut known: BTreeMap<_, _> = derived.iter()
+            .map(|name| (name, Variable::new(outer, Product::new(Default::default(), 1))))
+            .collect();
+
+        let underestimate: HashMap<_, _> = known.iter()
+            .map(|(name, variable)| ((*name).clone(), (**variable).clone()))
+            .collect();
+        let possible = least_fixpoint(outer, plan, &derived, &entered_inputs, &entered_base, &underestimate);
+        let next = least_fixpoint(outer, plan, &derived, &entered_inputs, &entered_base, &possible);
+
+        derived.iter()
+            .map(|name| {
+                let known = known.remove(name).unwrap().set(&next[name]).leave();
+                (name.clone(), known, possible[name].leave())
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut views: Vec<_> = base.into_iter()
+        .map(|(name, collection)| (name, collection, None))
+        .collect();
+    for (name, known, possible) in results {
+        let undefined = possible.concat(&known.negate());
+        views.push((name, known, Some(undefined)));
+    }
+    views
+}
+
+/// Evaluates the positive consequences of the rules for `derived` to a
+/// fixpoint, with every negated relation read from `negated` (or, for
+/// relations not defined by rules, from `base`).
+fn least_fixpoint<S>(
+    scope: &mut S,
+    plan: &ProgramPlan,
+    derived: &BTreeSet<Identifier>,
+    inputs: &HashMap<Identifier, Collection<S, Tuple>>,
+    base: &HashMap<Identifier, Collection<S, Tuple>>,
+    negated: &HashMap<Identifier, Collection<S, Tuple>>,
+) -> HashMap<Identifier, Collection<S, Tuple>>
+where
+    S: Scope,
+    S::Timestamp: Lattice + Ord,
+{
+    scope.iterative::<u32, _, _>(|inner| {
+        let base: HashMap<_, _> = base.iter()
+            .map(|(name, collection)| (name, collection.enter(inner)))
+            .collect();
+        let negated: HashMap<_, _> = negated.iter()
+            .map(|(name, collection)| (name, collection.enter(inner)))
+            .collect();
+        let mut variables: BTreeMap<_, _> = derived.iter()
+            .map(|name| (name, Variable::new(inner, Product::new(Default::default(), 1))))
+            .collect();
+
+        let mut results = Vec::new();
+        for name in derived {
+            let mut collection = inputs[name].enter(inner);
+            for rule in plan.rules_for(name) {
+                let rendered = render_rule(
+                    rule,
+                    |relation| match variables.get(relation) {
+                        Some(variable) => (**variable).clone(),
+                        None => base[relation].clone(),
+                    },
+                    |relation| match negated.get(relation) {
+                        Some(collection) => collection.clone(),
+                        None => base[relation].clone(),
+                    },
+                );
+                collection = collection.concat(&rendered);
+            }
+            results.push((name, collection.distinct()));
+        }
+
+        results.into_iter()
+            .map(|(name, collection)| {
+                let variable = variables.remove(name).unwrap();
+                (name.clone(), variable.set(&collection).leave())
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::{
+        engine::tests::{compile, relation, rows, tuples},
+        Semantics,
+    };
+
+    #[test]
+    fn alternating_fixpoint_leaves_the_positions_of_a_cycle_undefined() {
+        let engine = compile(
+            "Move(1, 2). Move(2, 3). Move(3, 1). Move(3, 4). Move(4, 5).\n.iterate {\n  Win(x) :- Move(x, y), !Win(y).\n}\n",
+            Semantics::WellFounded,
+        );
+
+        assert_eq!(tuples(&engine, "Win"), vec!["4"]);
+        let undefined = engine.undefined_tuples(&relation("Win")).unwrap().unwrap();
+        assert_eq!(rows(undefined), vec!["1", "2", "3"]);
+    }
+}
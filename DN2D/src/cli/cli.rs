@@ -1,24 +1,63 @@
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+
+use dn2d::{backend::Semantics, diagnostics::DiagnosticFormat};
 
 use crate::cli::export_to::ExportTo;
 
 #[derive(Parser, Debug)]
 #[command(name = "dn2d")]
 #[command(about = "Datalog with Negation to Differential Dataflow", long_about = None)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 pub struct Command {
+    #[command(subcommand)]
+    pub mode: Option<Mode>,
+
     #[arg(long, default_value = "none")]
     pub lex_as_json: ExportTo,
     
     #[arg(long, default_value = "none")]
     pub ast_as_json: ExportTo,
 
-    #[arg()]
-    pub src_path: PathBuf,
+    /// How negation through recursion is evaluated: "stratified" or "well-founded"
+    #[arg(long, default_value = "stratified", global = true)]
+    pub semantics: Semantics,
+
+    /// Require every relation to be declared with a .decl directive
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// How errors in the program are printed: "human" or "json", one object per line
+    #[arg(long, default_value = "human", global = true)]
+    pub diagnostics_format: DiagnosticFormat,
+
+    /// Keep running and re-evaluate incrementally whenever a .read file changes
+    #[arg(long)]
+    pub watch: bool,
+
+    #[arg(required = true)]
+    pub src_path: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Mode {
+    /// Load a program into a live dataflow and insert, retract and query facts interactively
+    Repl {
+        #[arg()]
+        src_path: PathBuf,
+    },
 }
 
 impl Command {
     pub fn new() -> Self{
         Command::parse()
     }
+
+    /// The program to run, given either directly or to a subcommand.
+    pub fn src_path(&self) -> &Path {
+        match &self.mode {
+            Some(Mode::Repl { src_path }) => src_path,
+            None => self.src_path.as_deref().expect("clap requires a source path without a subcommand"),
+        }
+    }
 }
@@ -13,7 +13,7 @@ impl ExportTo {
     pub fn handle(&self, json_str: String) {
         match self {
             ExportTo::Path(export_path) => {
-                fs::write(&export_path, json_str)
+                fs::write(export_path, json_str)
                     .unwrap_or_else(|err| {
                         panic!(
                             "Error: Could not write lex tokens json to file '{}': {}",
@@ -1,4 +1,5 @@
+#[allow(clippy::module_inception)]
 pub mod cli;
 pub mod export_to;
 
-pub use cli::Command;
+pub use cli::{Command, Mode};
@@ -0,0 +1,73 @@
+use std::fmt;
+
+/// The stable code of each kind of diagnostic, shown as `E0003` and the like.
+/// Codes are never reused or renumbered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    /// A character, literal or directive that the lexer does not recognise.
+    LexicalError,
+    /// Tokens that do not form a statement.
+    SyntaxError,
+    /// A variable that no positive atom of a rule body or query binds.
+    UnboundVariable,
+    /// A relation declared by more than one `.decl` directive.
+    DuplicateDeclaration,
+    /// A relation that is used but never defined.
+    UndefinedRelation,
+    /// A relation without a `.decl` directive, under `--strict`.
+    UndeclaredRelation,
+    /// A relation used with a different number of columns than elsewhere.
+    ArityMismatch,
+    /// Values of incompatible types in the same column or expression.
+    TypeMismatch,
+    /// A `.read` or `.write` directive with an unknown format.
+    UnknownFormat,
+    /// A relation defined recursively outside of an `.iterate` block.
+    RecursionOutsideIterate,
+    /// A relation depending negatively on itself, through negation or aggregation.
+    NegativeCycle,
+    /// A program that passes every check but that the engine cannot evaluate.
+    EngineError,
+    /// A file that cannot be read or written.
+    IoError,
+    /// An aggregate anywhere but as a whole term of a rule head.
+    MisplacedAggregate,
+    /// A term of an atom in a rule body or query that computes a value from
+    /// variables or wildcards.
+    ComputedAtomTerm,
+    /// A `.decl` or `.read` directive that names two columns the same.
+    DuplicateColumn,
+    /// A `.read` directive that names the columns of a declared relation
+    /// differently from its `.decl` directive.
+    ColumnMismatch,
+}
+
+impl Code {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Code::LexicalError => "E0001",
+            Code::SyntaxError => "E0002",
+            Code::UnboundVariable => "E0003",
+            Code::DuplicateDeclaration => "E0004",
+            Code::UndefinedRelation => "E0005",
+            Code::UndeclaredRelation => "E0006",
+            Code::ArityMismatch => "E0007",
+            Code::TypeMismatch => "E0008",
+            Code::UnknownFormat => "E0009",
+            Code::RecursionOutsideIterate => "E0010",
+            Code::NegativeCycle => "E0011",
+            Code::EngineError => "E0012",
+            Code::IoError => "E0013",
+            Code::MisplacedAggregate => "E0014",
+            Code::ComputedAtomTerm => "E0015",
+            Code::DuplicateColumn => "E0016",
+            Code::ColumnMismatch => "E0017",
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,347 @@
+use std::fmt;
+
+use serde_json::{Map, Value as Json};
+
+use crate::{diagnostics::Code, lexer::Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A span of the source that a diagnostic points at, with the line it is on.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub line_ref: String,
+    /// Whether the span is the problem itself rather than a location that
+    /// helps explain it.
+    pub primary: bool,
+}
+
+/// A problem found in a program, rendered with the lines of the source it
+/// points at:
+///
+/// ```text
+/// error[E0003]: Variable 'y' in the head of 'Path' is not bound by a positive atom of the rule body
+///  --> path.dn2d:4:9
+///   │
+/// 4 │ Path(x, y) :- Edge(x, z).
+///   │         ┻
+///   = help: ...
+/// ```
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub code: Code,
+    pub severity: Severity,
+    pub message: String,
+    /// The file the diagnostic is about, once known.
+    pub file: Option<String>,
+    pub labels: Vec<Label>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: Code, message: impl Into<String>) -> Self {
+        Diagnostic {
+            code,
+            severity: Severity::Error,
+            message: message.into(),
+            file: None,
+            labels: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    /// Points at the problem itself.
+    pub fn with_label(self, source: &str, span: Span, message: impl Into<String>) -> Self {
+        self.label(source, span, message.into(), true)
+    }
+
+    /// Points at a location that helps explain the problem.
+    pub fn with_secondary_label(self, source: &str, span: Span, message: impl Into<String>) -> Self {
+        self.label(source, span, message.into(), false)
+    }
+
+    pub fn with_help(mut self, message: impl Into<String>) -> Self {
+        self.help.push(message.into());
+        self
+    }
+
+    pub fn in_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// The span of the primary label, or of the first label if none is.
+    pub fn span(&self) -> Option<Span> {
+        self.labels.iter()
+            .find(|label| label.primary)
+            .or(self.labels.first())
+            .map(|label| label.span)
+    }
+
+    /// The diagnostic as a JSON object with its `code`, `severity`, `message`,
+    /// `file`, the location of its primary label, its `labels` with their own
+    /// locations, and its help as `suggestions`. A location is a `line` and
+    /// `start_column`, an `end_line` and `end_column`, all 1-based and
+    /// inclusive, and a `start_offset` and `end_offset` in bytes, the end
+    /// exclusive.
+    pub fn to_json(&self) -> Json {
+        let labels: Vec<Json> = self.labels.iter()
+            .map(|label| {
+                let mut object = location(label.span);
+                object.insert("primary".into(), label.primary.into());
+                object.insert("message".into(), label.message.clone().into());
+                Json::Object(object)
+            })
+            .collect();
+
+        let mut object = Map::new();
+        object.insert("code".into(), self.code.as_str().into());
+        object.insert("severity".into(), self.severity.to_string().into());
+        object.insert("message".into(), self.message.clone().into());
+        object.insert("file".into(), self.file.clone().into());
+        if let Some(span) = self.span() {
+            object.extend(location(span));
+        }
+        object.insert("labels".into(), labels.into());
+        object.insert("suggestions".into(), self.help.clone().into());
+        Json::Object(object)
+    }
+
+    /// Adds a label, or gives its message to an unlabelled one at the same span.
+    fn label(mut self, source: &str, span: Span, message: String, primary: bool) -> Self {
+        match self.labels.iter_mut().find(|label| label.span == span && label.message.is_empty()) {
+            Some(label) => label.message = message,
+            None => self.labels.push(Label { span, message, line_ref: source_line(source, span.start_line), primary }),
+        }
+        self
+    }
+}
+
+/// The text of the 1-based `line` of `source`, empty if there is no such line.
+pub fn source_line(source: &str, line: usize) -> String {
+    source.lines()
+        .nth(line.saturating_sub(1))
+        .unwrap_or_default()
+        .to_string()
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|label| label.span.start_offset);
+
+        let gutter = labels.last().map_or(0, |label| label.span.start_line.to_string().len());
+        let pad = " ".repeat(gutter);
+
+        match (&self.file, self.span()) {
+            (Some(file), Some(span)) => write!(f, "\n{}--> {}:{}:{}", pad, file, span.start_line, span.start_column)?,
+            (Some(file), None) => write!(f, "\n{}--> {}", pad, file)?,
+            (None, Some(span)) => write!(f, "\n{}--> {}:{}", pad, span.start_line, span.start_column)?,
+            (None, None) => {}
+        }
+
+        let mut previous_line = None;
+        for label in labels {
+            let line = label.span.start_line;
+            if previous_line != Some(line) {
+                match previous_line {
+                    None => write!(f, "\n{} │", pad)?,
+                    Some(previous) if line > previous + 1 => write!(f, "\n{} ┆", pad)?,
+                    Some(_) => {}
+                }
+                write!(f, "\n{:>width$} │ {}", line, label.line_ref, width = gutter)?;
+                previous_line = Some(line);
+            }
+            write!(f, "\n{} │ {}{}", pad, indent(&label.line_ref, label.span.start_column), underline(label))?;
+            if !label.message.is_empty() {
+                write!(f, " {}", label.message)?;
+            }
+        }
+
+        for help in &self.help {
+            write!(f, "\n{} = help: {}", pad, help)?;
+        }
+        Ok(())
+    }
+}
+
+fn location(span: Span) -> Map<String, Json> {
+    let mut object = Map::new();
+    object.insert("line".into(), span.start_line.into());
+    object.insert("start_column".into(), span.start_column.into());
+    object.insert("end_line".into(), span.end_line.into());
+    object.insert("end_column".into(), span.end_column.into());
+    object.insert("start_offset".into(), span.start_offset.into());
+    object.insert("end_offset".into(), span.end_offset.into());
+    object
+}
+
+/// The whitespace up to column `start` of `line`, keeping its tabs so that
+/// the underline stays aligned.
+fn indent(line: &str, start: usize) -> String {
+    let mut chars = line.chars();
+    (1..start)
+        .map(|_| if chars.next() == Some('\t') { '\t' } else { ' ' })
+        .collect()
+}
+
+/// The underline of a label, under its first line only for spans that cover
+/// several lines.
+fn underline(label: &Label) -> String {
+    let span = label.span;
+    let line_end = label.line_ref.chars().count().max(span.start_column);
+    let end = if span.end_line > span.start_line { line_end } else { span.end_column.min(line_end) };
+    let width = (end + 1).saturating_sub(span.start_column).max(1);
+    let (single, left, middle, right) = if label.primary {
+        ('┻', '┗', '━', '┛')
+    } else {
+        ('┴', '└', '─', '┘')
+    };
+
+    if width == 1 {
+        single.to_string()
+    } else {
+        let mut underline = String::from(left);
+        underline.extend(std::iter::repeat_n(middle, width - 2));
+        underline.push(right);
+        underline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The span of the `nth` occurrence of `text` in `source`, which must not
+    /// cross a line.
+    fn span(source: &str, text: &str, nth: usize) -> Span {
+        let start_offset = source.match_indices(text).nth(nth).unwrap().0;
+        let before = &source[..start_offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before[before.rfind('\n').map_or(0, |newline| newline + 1)..].chars().count() + 1;
+        Span {
+            start_offset,
+            end_offset: start_offset + text.len(),
+            start_line: line,
+            start_column: column,
+            end_line: line,
+            end_column: column + text.chars().count() - 1,
+        }
+    }
+
+    const SOURCE: &str = "Edge(1, 2).\nPath(x, y) :- Edge(x, z).\n\n\n\n\n\n\n\n\nPath(x, y) :- Path(x, w).\n";
+
+    #[test]
+    fn renders_the_lines_of_every_label() {
+        let diagnostic = Diagnostic::error(Code::UnboundVariable, "Variable 'y' is not bound")
+            .with_label(SOURCE, span(SOURCE, "y", 1), "")
+            .with_secondary_label(SOURCE, span(SOURCE, "Edge", 1), "no 'y' here")
+            .with_secondary_label(SOURCE, span(SOURCE, "Edge", 0), "declared here")
+            .with_help("Bind 'y' in the body")
+            .in_file("path.dn2d");
+
+        assert_eq!(diagnostic.to_string(), [
+            "error[E0003]: Variable 'y' is not bound",
+            "  --> path.dn2d:11:9",
+            "   │",
+            " 1 │ Edge(1, 2).",
+            "   │ └──┘ declared here",
+            " 2 │ Path(x, y) :- Edge(x, z).",
+            "   │               └──┘ no 'y' here",
+            "   ┆",
+            "11 │ Path(x, y) :- Path(x, w).",
+            "   │         ┻",
+            "   = help: Bind 'y' in the body",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn gives_a_message_to_the_unlabelled_label_at_the_same_span() {
+        let diagnostic = Diagnostic::error(Code::TypeMismatch, "Mismatch")
+            .with_label(SOURCE, span(SOURCE, "Edge", 0), "")
+            .with_secondary_label(SOURCE, span(SOURCE, "Edge", 0), "here");
+        assert_eq!(diagnostic.labels.len(), 1);
+        assert_eq!(diagnostic.to_string(), "error[E0008]: Mismatch\n --> 1:1\n  │\n1 │ Edge(1, 2).\n  │ ┗━━┛ here");
+    }
+
+    #[test]
+    fn keeps_tabs_and_stops_underlines_at_the_end_of_the_line() {
+        let source = "\tP(x) :- E(x,\n\t  y).\n";
+        let mut multiline = span(source, "E(x,", 0);
+        multiline.end_line = 2;
+        multiline.end_column = 5;
+        let diagnostic = Diagnostic::error(Code::SyntaxError, "Unfinished").with_label(source, multiline, "");
+        assert_eq!(diagnostic.to_string(), "error[E0002]: Unfinished\n --> 1:10\n  │\n1 │ \tP(x) :- E(x,\n  │ \t        ┗━━┛");
+    }
+
+    #[test]
+    fn renders_a_diagnostic_without_a_location() {
+        let diagnostic = Diagnostic::error(Code::IoError, "Could not read file 'x.csv'");
+        assert_eq!(diagnostic.to_string(), "error[E0013]: Could not read file 'x.csv'");
+        assert_eq!(diagnostic.in_file("p.dn2d").to_string(), "error[E0013]: Could not read file 'x.csv'\n--> p.dn2d");
+    }
+
+    #[test]
+    fn json_has_the_location_of_the_primary_label_and_every_label() {
+        let diagnostic = Diagnostic::error(Code::UnboundVariable, "Variable 'y' is not bound")
+            .with_secondary_label(SOURCE, span(SOURCE, "Edge", 1), "no 'y' here")
+            .with_label(SOURCE, span(SOURCE, "y", 1), "")
+            .with_help("Bind 'y' in the body")
+            .in_file("path.dn2d");
+
+        assert_eq!(diagnostic.to_json(), serde_json::json!({
+            "code": "E0003",
+            "severity": "error",
+            "message": "Variable 'y' is not bound",
+            "file": "path.dn2d",
+            "line": 11,
+            "start_column": 9,
+            "end_line": 11,
+            "end_column": 9,
+            "start_offset": 54,
+            "end_offset": 55,
+            "labels": [
+                {
+                    "line": 2, "start_column": 15, "end_line": 2, "end_column": 18,
+                    "start_offset": 26, "end_offset": 30,
+                    "primary": false, "message": "no 'y' here",
+                },
+                {
+                    "line": 11, "start_column": 9, "end_line": 11, "end_column": 9,
+                    "start_offset": 54, "end_offset": 55,
+                    "primary": true, "message": "",
+                },
+            ],
+            "suggestions": ["Bind 'y' in the body"],
+        }));
+    }
+
+    #[test]
+    fn json_of_a_diagnostic_without_a_location_has_no_location_keys() {
+        let json = Diagnostic::error(Code::IoError, "Could not read file 'x.csv'").to_json();
+        assert_eq!(json, serde_json::json!({
+            "code": "E0013",
+            "severity": "error",
+            "message": "Could not read file 'x.csv'",
+            "file": null,
+            "labels": [],
+            "suggestions": [],
+        }));
+    }
+}
@@ -0,0 +1,31 @@
+use std::{fmt, str::FromStr};
+
+/// How diagnostics are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticFormat {
+    /// With the lines of the source they point at, for people.
+    Human,
+    /// As one JSON object per line, for tools.
+    Json,
+}
+
+impl FromStr for DiagnosticFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "human" => Ok(DiagnosticFormat::Human),
+            "json" => Ok(DiagnosticFormat::Json),
+            other => Err(format!("Unknown diagnostics format '{}', expected 'human' or 'json'", other)),
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticFormat::Human => write!(f, "human"),
+            DiagnosticFormat::Json => write!(f, "json"),
+        }
+    }
+}
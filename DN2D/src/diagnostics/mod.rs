@@ -0,0 +1,7 @@
+pub mod code;
+pub mod diagnostic;
+pub mod format;
+
+pub use code::Code;
+pub use diagnostic::{source_line, Diagnostic, Label, Severity};
+pub use format::DiagnosticFormat;
@@ -0,0 +1,348 @@
+use std::{fs, io::Write, path::Path};
+
+use crate::{
+    backend::{value::Float, Change, Tuple, Value},
+    io::{ChangeWriter, IoError, Reader, Writer},
+    semantic::{RelationSchema, Type},
+};
+
+/// Comma-separated values, one tuple per line and without a header.
+pub struct Csv;
+
+impl Reader for Csv {
+    fn read(&self, path: &Path, schema: &RelationSchema) -> Result<Vec<Tuple>, IoError> {
+        read_csv(path, &schema.types)
+    }
+}
+
+impl Writer for Csv {
+    fn write(&self, out: &mut dyn Write, _schema: &RelationSchema, tuples: &[Tuple]) -> std::io::Result<()> {
+        write_csv(out, tuples)
+    }
+}
+
+/// Comma-separated values whose first line names the columns.
+///
+/// When reading, the header may list the relation's columns in any order.
+pub struct CsvWithHeader;
+
+impl Reader for CsvWithHeader {
+    fn read(&self, path: &Path, schema: &RelationSchema) -> Result<Vec<Tuple>, IoError> {
+        read_csv_with_header(path, &schema.columns, &schema.types)
+    }
+}
+
+impl Writer for CsvWithHeader {
+    fn write(&self, out: &mut dyn Write, schema: &RelationSchema, tuples: &[Tuple]) -> std::io::Result<()> {
+        writeln!(out, "{}", schema.columns.join(","))?;
+        write_csv(out, tuples)
+    }
+}
+
+/// Comma-separated changes, one per line: the fields of the tuple followed by
+/// the epoch and the diff, e.g. `1,2,3,+1` or `1,2,4,-1`.
+pub struct CsvChanges;
+
+impl ChangeWriter for CsvChanges {
+    fn write_changes(&self, out: &mut dyn Write, _schema: &RelationSchema, changes: &[Change]) -> std::io::Result<()> {
+        for change in changes {
+            let fields: Vec<String> = change.tuple.iter().map(format_field).collect();
+            writeln!(out, "{},{},{:+}", fields.join(","), change.epoch, change.diff)?;
+        }
+        Ok(())
+    }
+}
+
+/// A field of a CSV record, and whether it was quoted.
+#[derive(Debug)]
+struct Field {
+    text: String,
+    quoted: bool,
+}
+
+/// Reads one tuple per non-empty line of `path`.
+///
+/// Each line must have one field per entry of `types`. Fields are parsed as
+/// the type of their column, or, for columns of unknown type, as a quoted
+/// string, an integer, a float, a boolean or else an unquoted string.
+pub fn read_csv(path: &Path, types: &[Option<Type>]) -> Result<Vec<Tuple>, IoError> {
+    let content = read_file(path)?;
+    let positions: Vec<usize> = (0..types.len()).collect();
+    read_records(path, lines(&content), &positions, types)
+}
+
+/// Reads a header line and then one tuple per non-empty line of `path`.
+///
+/// The header must name each of `columns` exactly once, in any order, and
+/// nothing else. Fields are parsed as in [`read_csv`].
+pub fn read_csv_with_header(path: &Path, columns: &[String], types: &[Option<Type>]) -> Result<Vec<Tuple>, IoError> {
+    let content = read_file(path)?;
+    let mut lines = lines(&content);
+
+    let Some((number, header)) = lines.next() else {
+        return Err(IoError::new(path, format!("Expected a header with the columns {}", columns.join(", "))));
+    };
+    let positions = split_record(header)
+        .and_then(|header| header_positions(&header, columns))
+        .map_err(|message| IoError::at_line(path, number, message))?;
+
+    read_records(path, lines, &positions, types)
+}
+
+pub fn write_csv(out: &mut dyn Write, tuples: &[Tuple]) -> std::io::Result<()> {
+    for tuple in tuples {
+        let fields: Vec<String> = tuple.iter().map(format_field).collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+pub fn read_file(path: &Path) -> Result<String, IoError> {
+    fs::read_to_string(path)
+        .map_err(|err| IoError::new(path, format!("Could not read file: {}", err)))
+}
+
+/// The non-empty lines of `content` with their line numbers.
+pub fn lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, line))
+}
+
+/// Parses each line with [`parse_record`].
+fn read_records<'a>(
+    path: &Path,
+    lines: impl Iterator<Item = (usize, &'a str)>,
+    positions: &[usize],
+    types: &[Option<Type>],
+) -> Result<Vec<Tuple>, IoError> {
+    lines
+        .map(|(number, line)| parse_record(line, positions, types)
+            .map_err(|message| IoError::at_line(path, number, message)))
+        .collect()
+}
+
+/// Parses a line as a record with one field per entry of `types`, as
+/// [`read_csv`] does.
+pub fn parse_tuple(line: &str, types: &[Option<Type>]) -> Result<Tuple, String> {
+    let positions: Vec<usize> = (0..types.len()).collect();
+    parse_record(line, &positions, types)
+}
+
+/// Parses a line as a record of `positions.len()` fields, the `i`-th column
+/// of the tuple being the field at `positions[i]`.
+fn parse_record(line: &str, positions: &[usize], types: &[Option<Type>]) -> Result<Tuple, String> {
+    let mut fields: Vec<Option<Field>> = split_record(line)?
+        .into_iter()
+        .map(Some)
+        .collect();
+    if fields.len() != positions.len() {
+        return Err(format!("Expected {} columns but found {}", positions.len(), fields.len()));
+    }
+
+    positions.iter()
+        .zip(types)
+        .map(|(&position, ty)| parse_field(fields[position].take().unwrap(), *ty)
+            .map_err(|message| format!("Column {}: {}", position + 1, message)))
+        .collect()
+}
+
+/// Finds the position of each of `columns` in `header`.
+fn header_positions(header: &[Field], columns: &[String]) -> Result<Vec<usize>, String> {
+    for (position, field) in header.iter().enumerate() {
+        if !columns.contains(&field.text) {
+            return Err(format!(
+                "Unexpected column '{}' in the header, expected one of: {}", field.text, columns.join(", ")
+            ));
+        }
+        if header[..position].iter().any(|earlier| earlier.text == field.text) {
+            return Err(format!("Column '{}' appears more than once in the header", field.text));
+        }
+    }
+
+    columns.iter()
+        .map(|column| header.iter()
+            .position(|field| field.text == *column)
+            .ok_or_else(|| format!("Column '{}' is missing from the header", column)))
+        .collect()
+}
+
+/// Splits a line into fields separated by commas. A field may be enclosed in
+/// double quotes, in which case it can contain commas and `""` stands for `"`.
+fn split_record(line: &str) -> Result<Vec<Field>, String> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+
+        let field = if chars.next_if_eq(&'"').is_some() {
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.next_if_eq(&'"').is_some() => text.push('"'),
+                    Some('"') => break,
+                    Some(c) => text.push(c),
+                    None => return Err("Unterminated quoted field".to_string()),
+                }
+            }
+            while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+            if chars.peek().is_some_and(|c| *c != ',') {
+                return Err(format!("Unexpected '{}' after a quoted field", chars.peek().unwrap()));
+            }
+            Field { text, quoted: true }
+        } else {
+            let mut text = String::new();
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                text.push(c);
+            }
+            Field { text: text.trim_end().to_string(), quoted: false }
+        };
+        fields.push(field);
+
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
+}
+
+fn parse_field(field: Field, ty: Option<Type>) -> Result<Value, String> {
+    let Field { text, quoted } = field;
+    let Some(ty) = ty else {
+        if quoted {
+            return Ok(Value::String(text));
+        }
+        return Ok(parse_number(&text)
+            .or_else(|| text.parse().ok().map(Value::Boolean))
+            .unwrap_or(Value::String(text)));
+    };
+
+    let value = match ty {
+        Type::String => return Ok(Value::String(text)),
+        _ if quoted => None,
+        Type::Integer => text.parse().ok().map(Value::Integer),
+        Type::Float => text.parse().ok().map(|f| Value::Float(Float(f))),
+        Type::Number => parse_number(&text),
+        Type::Boolean => text.parse().ok().map(Value::Boolean),
+    };
+
+    value.ok_or_else(|| {
+        let found = if quoted { format!("\"{}\"", text) } else { format!("'{}'", text) };
+        format!("Expected {} but found {}", ty.with_article(), found)
+    })
+}
+
+fn parse_number(text: &str) -> Option<Value> {
+    text.parse().map(Value::Integer).ok()
+        .or_else(|| text.parse().ok().map(|f| Value::Float(Float(f))))
+}
+
+fn format_field(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("\"{}\"", s.replace('"', "\"\"")),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Identifier, lexer::Span};
+
+    fn schema() -> RelationSchema {
+        let span = Span { start_offset: 0, end_offset: 1, start_line: 1, start_column: 1, end_line: 1, end_column: 1 };
+        RelationSchema {
+            name: Identifier::new("Label", span),
+            arity: 2,
+            columns: vec!["id".to_string(), "text".to_string()],
+            types: vec![Some(Type::Integer), Some(Type::String)],
+            declared: true,
+        }
+    }
+
+    fn split(line: &str) -> Vec<(String, bool)> {
+        split_record(line).unwrap().into_iter().map(|field| (field.text, field.quoted)).collect()
+    }
+
+    fn field(text: &str, quoted: bool) -> (String, bool) {
+        (text.to_string(), quoted)
+    }
+
+    #[test]
+    fn split_record_trims_unquoted_fields() {
+        assert_eq!(split(" 1, two words ,\t3"), [field("1", false), field("two words", false), field("3", false)]);
+        assert_eq!(split("1,,"), [field("1", false), field("", false), field("", false)]);
+    }
+
+    #[test]
+    fn split_record_keeps_commas_and_escaped_quotes_in_quoted_fields() {
+        assert_eq!(split(r#""a, b" , "say ""hi""""#), [field("a, b", true), field(r#"say "hi""#, true)]);
+    }
+
+    #[test]
+    fn split_record_rejects_malformed_quoted_fields() {
+        assert_eq!(split_record(r#""open"#).unwrap_err(), "Unterminated quoted field");
+        assert_eq!(split_record(r#""a" b"#).unwrap_err(), "Unexpected 'b' after a quoted field");
+    }
+
+    #[test]
+    fn parse_tuple_converts_fields_to_column_types() {
+        let types = [Some(Type::Integer), Some(Type::Float), Some(Type::String), None];
+        assert_eq!(
+            parse_tuple(r#"1, 2, "x", true"#, &types).unwrap(),
+            vec![Value::Integer(1), Value::Float(Float(2.0)), Value::String("x".to_string()), Value::Boolean(true)]
+        );
+        assert_eq!(
+            parse_tuple(r#""1", 2, x, y"#, &types).unwrap_err(),
+            r#"Column 1: Expected an integer but found "1""#
+        );
+        assert_eq!(parse_tuple("1, 2", &types).unwrap_err(), "Expected 4 columns but found 2");
+    }
+
+    fn positions(header: &str) -> Result<Vec<usize>, String> {
+        let columns = ["id".to_string(), "name".to_string()];
+        header_positions(&split_record(header).unwrap(), &columns)
+    }
+
+    #[test]
+    fn header_positions_follow_the_order_of_the_header() {
+        assert_eq!(positions("id, name").unwrap(), [0, 1]);
+        assert_eq!(positions(r#""name",id"#).unwrap(), [1, 0]);
+        assert_eq!(
+            parse_record(r#""Ann", 7"#, &positions("name,id").unwrap(), &[Some(Type::Integer), Some(Type::String)]).unwrap(),
+            vec![Value::Integer(7), Value::String("Ann".to_string())]
+        );
+    }
+
+    #[test]
+    fn header_positions_reject_unknown_repeated_and_missing_columns() {
+        assert_eq!(positions("id,age").unwrap_err(), "Unexpected column 'age' in the header, expected one of: id, name");
+        assert_eq!(positions("id,id").unwrap_err(), "Column 'id' appears more than once in the header");
+        assert_eq!(positions("name").unwrap_err(), "Column 'id' is missing from the header");
+    }
+
+    #[test]
+    fn change_lines_read_back_as_the_tuple_epoch_and_diff() {
+        let changes = vec![
+            Change { tuple: vec![Value::Integer(1), Value::String("a, \"b\"".to_string())], epoch: 0, diff: 1 },
+            Change { tuple: vec![Value::Integer(-2), Value::String("c".to_string())], epoch: 3, diff: -1 },
+        ];
+        let mut out = Vec::new();
+        CsvChanges.write_changes(&mut out, &schema(), &changes).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text, "1,\"a, \"\"b\"\"\",0,+1\n-2,\"c\",3,-1\n");
+
+        let types = [Some(Type::Integer), Some(Type::String), Some(Type::Integer), Some(Type::Integer)];
+        let read: Vec<Change> = text.lines()
+            .map(|line| {
+                let mut fields = parse_tuple(line, &types).unwrap();
+                let (Some(Value::Integer(diff)), Some(Value::Integer(epoch))) = (fields.pop(), fields.pop()) else {
+                    panic!("Expected an epoch and a diff in '{}'", line);
+                };
+                Change { tuple: fields, epoch: epoch as u64, diff: diff as isize }
+            })
+            .collect();
+        assert_eq!(read, changes);
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::Path;
+
+use crate::{
+    backend::Change,
+    io::{csv::{lines, parse_tuple, read_file}, ChangeReader, IoError},
+    semantic::RelationSchema,
+};
+
+/// A log of insertions and retractions, one per line: an optional epoch, `+`
+/// or `-`, and the fields of the tuple as in CSV, e.g. `+ 1,2`, `- 2,3` or
+/// `4 + 1,3`. A line without an epoch belongs to the epoch of the line before
+/// it, or to epoch 0 if it is the first.
+pub struct Delta;
+
+impl ChangeReader for Delta {
+    fn read_changes(&self, path: &Path, schema: &RelationSchema) -> Result<Vec<Change>, IoError> {
+        let content = read_file(path)?;
+
+        let mut changes = Vec::new();
+        let mut epoch = 0;
+        for (number, line) in lines(&content) {
+            let change = parse_change(line, epoch, schema)
+                .map_err(|message| IoError::at_line(path, number, message))?;
+            epoch = change.epoch;
+            changes.push(change);
+        }
+        Ok(changes)
+    }
+}
+
+fn parse_change(line: &str, epoch: u64, schema: &RelationSchema) -> Result<Change, String> {
+    let line = line.trim_start();
+    let digits = line.find(|c: char| !c.is_ascii_digit()).unwrap_or(line.len());
+    let (epoch, rest) = match digits {
+        0 => (epoch, line),
+        _ => {
+            // The last epoch is never closed, as no later one follows it.
+            let epoch = line[..digits].parse()
+                .ok()
+                .filter(|epoch| *epoch < u64::MAX)
+                .ok_or_else(|| format!("Epoch '{}' is too large", &line[..digits]))?;
+            (epoch, line[digits..].trim_start())
+        }
+    };
+
+    let (diff, fields) = match rest.chars().next() {
+        Some('+') => (1, &rest[1..]),
+        Some('-') => (-1, &rest[1..]),
+        _ => return Err("Expected '+' or '-' before the tuple".to_string()),
+    };
+
+    let tuple = parse_tuple(fields, &schema.types)?;
+    Ok(Change { tuple, epoch, diff })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Identifier, backend::Value, lexer::Span, semantic::Type};
+
+    fn schema() -> RelationSchema {
+        let span = Span { start_offset: 0, end_offset: 1, start_line: 1, start_column: 1, end_line: 1, end_column: 1 };
+        RelationSchema {
+            name: Identifier::new("Edge", span),
+            arity: 2,
+            columns: vec!["a".to_string(), "b".to_string()],
+            types: vec![Some(Type::Integer), Some(Type::Integer)],
+            declared: true,
+        }
+    }
+
+    #[test]
+    fn parse_change_keeps_the_epoch_of_the_line_before() {
+        assert_eq!(
+            parse_change("+ 1,2", 3, &schema()).unwrap(),
+            Change { tuple: vec![Value::Integer(1), Value::Integer(2)], epoch: 3, diff: 1 }
+        );
+    }
+
+    #[test]
+    fn parse_change_reads_an_epoch_before_the_sign() {
+        assert_eq!(
+            parse_change("  4 - 1, 3", 3, &schema()).unwrap(),
+            Change { tuple: vec![Value::Integer(1), Value::Integer(3)], epoch: 4, diff: -1 }
+        );
+    }
+
+    #[test]
+    fn parse_change_rejects_epochs_that_cannot_be_closed() {
+        assert_eq!(
+            parse_change("18446744073709551614 + 1,2", 0, &schema()).unwrap().epoch,
+            u64::MAX - 1
+        );
+        assert_eq!(
+            parse_change("18446744073709551615 + 1,2", 0, &schema()).unwrap_err(),
+            "Epoch '18446744073709551615' is too large"
+        );
+        assert_eq!(
+            parse_change("99999999999999999999 + 1,2", 0, &schema()).unwrap_err(),
+            "Epoch '99999999999999999999' is too large"
+        );
+    }
+
+    #[test]
+    fn parse_change_requires_a_sign() {
+        assert_eq!(parse_change("1,2", 0, &schema()).unwrap_err(), "Expected '+' or '-' before the tuple");
+    }
+}
@@ -0,0 +1,136 @@
+use std::{collections::{BTreeMap, BTreeSet}, io::Write, path::Path};
+
+use crate::{
+    backend::{Change, Tuple},
+    io::{csv::{Csv, CsvChanges, CsvWithHeader}, delta::Delta, jsonl::{Jsonl, JsonlChanges}, table::Table, txt::Txt, IoError},
+    semantic::RelationSchema,
+};
+
+/// Loads the tuples of a relation named in a `.read` directive.
+pub trait Reader {
+    /// Reads every tuple of the relation described by `schema` from `path`.
+    fn read(&self, path: &Path, schema: &RelationSchema) -> Result<Vec<Tuple>, IoError>;
+}
+
+/// Loads the insertions into and retractions from a relation named in a
+/// `.read` directive, which are fed into the engine epoch by epoch.
+pub trait ChangeReader {
+    /// Reads every change to the relation described by `schema` from `path`.
+    fn read_changes(&self, path: &Path, schema: &RelationSchema) -> Result<Vec<Change>, IoError>;
+}
+
+/// Stores the tuples of a relation named in a `.write` directive.
+pub trait Writer {
+    /// Writes `tuples`, which are sorted, of the relation described by `schema`.
+    fn write(&self, out: &mut dyn Write, schema: &RelationSchema, tuples: &[Tuple]) -> std::io::Result<()>;
+}
+
+/// Stores the changes to a relation named in a `.write` directive, rather than
+/// its contents, so that consumers can apply them as deltas.
+pub trait ChangeWriter {
+    /// Writes `changes`, which are ordered by epoch and then by tuple, to the
+    /// relation described by `schema`.
+    fn write_changes(&self, out: &mut dyn Write, schema: &RelationSchema, changes: &[Change]) -> std::io::Result<()>;
+}
+
+/// The readers and writers available to `.read` and `.write` directives, keyed
+/// by the format string that follows `as`.
+///
+/// [`FormatRegistry::default`] contains the built-in formats; programs
+/// embedding DN2D can register their own alongside them.
+pub struct FormatRegistry {
+    readers: BTreeMap<String, Box<dyn Reader>>,
+    change_readers: BTreeMap<String, Box<dyn ChangeReader>>,
+    writers: BTreeMap<String, Box<dyn Writer>>,
+    change_writers: BTreeMap<String, Box<dyn ChangeWriter>>,
+}
+
+impl FormatRegistry {
+    /// A registry without any format.
+    pub fn empty() -> Self {
+        FormatRegistry {
+            readers: BTreeMap::new(),
+            change_readers: BTreeMap::new(),
+            writers: BTreeMap::new(),
+            change_writers: BTreeMap::new(),
+        }
+    }
+
+    /// Makes `reader` available as `.read ... as "<format>"`, replacing any
+    /// reader previously registered for `format`.
+    pub fn register_reader(&mut self, format: impl Into<String>, reader: impl Reader + 'static) {
+        self.readers.insert(format.into(), Box::new(reader));
+    }
+
+    /// Makes `reader` available as `.read ... as "<format>"`, replacing any
+    /// change reader previously registered for `format`. A format with both a
+    /// reader and a change reader reads tuples.
+    pub fn register_change_reader(&mut self, format: impl Into<String>, reader: impl ChangeReader + 'static) {
+        self.change_readers.insert(format.into(), Box::new(reader));
+    }
+
+    /// Makes `writer` available as `.write ... as "<format>"`, replacing any
+    /// writer previously registered for `format`.
+    pub fn register_writer(&mut self, format: impl Into<String>, writer: impl Writer + 'static) {
+        self.writers.insert(format.into(), Box::new(writer));
+    }
+
+    /// Makes `writer` available as `.write ... as "<format>"`, replacing any
+    /// change writer previously registered for `format`. A format with both a
+    /// writer and a change writer writes snapshots.
+    pub fn register_change_writer(&mut self, format: impl Into<String>, writer: impl ChangeWriter + 'static) {
+        self.change_writers.insert(format.into(), Box::new(writer));
+    }
+
+    pub fn reader(&self, format: &str) -> Option<&dyn Reader> {
+        self.readers.get(format).map(|reader| reader.as_ref())
+    }
+
+    pub fn change_reader(&self, format: &str) -> Option<&dyn ChangeReader> {
+        self.change_readers.get(format).map(|reader| reader.as_ref())
+    }
+
+    pub fn writer(&self, format: &str) -> Option<&dyn Writer> {
+        self.writers.get(format).map(|writer| writer.as_ref())
+    }
+
+    pub fn change_writer(&self, format: &str) -> Option<&dyn ChangeWriter> {
+        self.change_writers.get(format).map(|writer| writer.as_ref())
+    }
+
+    /// The formats with a reader or a change reader, in sorted order.
+    pub fn reader_formats(&self) -> impl Iterator<Item = &str> {
+        let formats: BTreeSet<&str> = self.readers.keys()
+            .chain(self.change_readers.keys())
+            .map(String::as_str)
+            .collect();
+        formats.into_iter()
+    }
+
+    /// The formats with a writer or a change writer, in sorted order.
+    pub fn writer_formats(&self) -> impl Iterator<Item = &str> {
+        let formats: BTreeSet<&str> = self.writers.keys()
+            .chain(self.change_writers.keys())
+            .map(String::as_str)
+            .collect();
+        formats.into_iter()
+    }
+}
+
+impl Default for FormatRegistry {
+    fn default() -> Self {
+        let mut registry = FormatRegistry::empty();
+        registry.register_reader("csv", Csv);
+        registry.register_writer("csv", Csv);
+        registry.register_reader("csv_with_header", CsvWithHeader);
+        registry.register_writer("csv_with_header", CsvWithHeader);
+        registry.register_reader("jsonl", Jsonl);
+        registry.register_writer("jsonl", Jsonl);
+        registry.register_writer("table", Table);
+        registry.register_writer("txt", Txt);
+        registry.register_change_reader("delta", Delta);
+        registry.register_change_writer("csv_changes", CsvChanges);
+        registry.register_change_writer("jsonl_changes", JsonlChanges);
+        registry
+    }
+}
@@ -0,0 +1,31 @@
+use std::{fmt, path::{Path, PathBuf}};
+
+/// A failure to read or write a relation, with the file and, for malformed
+/// input, the line it happened at.
+#[derive(Debug)]
+pub struct IoError {
+    pub message: String,
+    pub path: PathBuf,
+    pub line: Option<usize>,
+}
+
+impl IoError {
+    pub fn new(path: &Path, message: impl Into<String>) -> Self {
+        IoError { message: message.into(), path: path.to_path_buf(), line: None }
+    }
+
+    pub fn at_line(path: &Path, line: usize, message: impl Into<String>) -> Self {
+        IoError { message: message.into(), path: path.to_path_buf(), line: Some(line) }
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for IoError {}
@@ -0,0 +1,210 @@
+use std::{fs, io::Write, path::Path};
+
+use serde_json::{Map, Number, Value as Json};
+
+use crate::{
+    backend::{value::Float, Change, Tuple, Value},
+    io::{ChangeWriter, IoError, Reader, Writer},
+    semantic::{RelationSchema, Type},
+};
+
+/// JSON Lines, one JSON value per tuple and line.
+///
+/// A tuple is read from an object whose keys are the relation's column names,
+/// or from an array with one element per column. Tuples are written as
+/// objects keyed by the column names, in column order.
+pub struct Jsonl;
+
+impl Reader for Jsonl {
+    fn read(&self, path: &Path, schema: &RelationSchema) -> Result<Vec<Tuple>, IoError> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| IoError::new(path, format!("Could not read file: {}", err)))?;
+
+        let mut tuples = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let tuple = serde_json::from_str(line)
+                .map_err(|err| format!("Invalid JSON: {}", err))
+                .and_then(|json| parse_tuple(json, schema))
+                .map_err(|message| IoError::at_line(path, index + 1, message))?;
+            tuples.push(tuple);
+        }
+        Ok(tuples)
+    }
+}
+
+impl Writer for Jsonl {
+    fn write(&self, out: &mut dyn Write, schema: &RelationSchema, tuples: &[Tuple]) -> std::io::Result<()> {
+        for tuple in tuples {
+            writeln!(out, "{}", to_object(schema, tuple))?;
+        }
+        Ok(())
+    }
+}
+
+/// JSON Lines changes, one object per line with the tuple as written by
+/// [`Jsonl`], the epoch and the diff, e.g.
+/// `{"tuple":{"x":1,"y":2},"epoch":3,"diff":-1}`.
+pub struct JsonlChanges;
+
+impl ChangeWriter for JsonlChanges {
+    fn write_changes(&self, out: &mut dyn Write, schema: &RelationSchema, changes: &[Change]) -> std::io::Result<()> {
+        for change in changes {
+            writeln!(
+                out,
+                "{{\"tuple\":{},\"epoch\":{},\"diff\":{}}}",
+                to_object(schema, &change.tuple), change.epoch, change.diff
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_tuple(json: Json, schema: &RelationSchema) -> Result<Tuple, String> {
+    let fields = match json {
+        Json::Object(mut object) => object_fields(&mut object, schema)?,
+        Json::Array(array) if array.len() == schema.arity => array,
+        Json::Array(array) => {
+            return Err(format!("Expected {} columns but found {}", schema.arity, array.len()));
+        }
+        other => return Err(format!("Expected an object or an array but found {}", other)),
+    };
+
+    fields.into_iter()
+        .zip(&schema.types)
+        .enumerate()
+        .map(|(column, (json, ty))| from_json(json, *ty)
+            .map_err(|message| format!("Column {}: {}", column + 1, message)))
+        .collect()
+}
+
+fn object_fields(object: &mut Map<String, Json>, schema: &RelationSchema) -> Result<Vec<Json>, String> {
+    schema.columns.iter()
+        .map(|column| object.remove(column).ok_or_else(|| format!("Missing key '{}'", column)))
+        .collect()
+}
+
+fn from_json(json: Json, ty: Option<Type>) -> Result<Value, String> {
+    let value = match &json {
+        Json::Bool(b) => Value::Boolean(*b),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Float(Float(n.as_f64().unwrap_or(f64::NAN))),
+        },
+        Json::String(s) => Value::String(s.clone()),
+        _ => return Err(format!("Expected a boolean, a number or a string but found {}", json)),
+    };
+
+    match ty {
+        None => Ok(value),
+        Some(ty) => value.conform(ty)
+            .ok_or_else(|| format!("Expected {} but found {}", ty.with_article(), json)),
+    }
+}
+
+/// Writes `tuple` as an object keyed by the column names of `schema`. Built
+/// field by field, since a `Map` does not keep the column order.
+fn to_object(schema: &RelationSchema, tuple: &Tuple) -> String {
+    let fields: Vec<String> = schema.columns.iter()
+        .zip(tuple)
+        .map(|(column, value)| format!("{}:{}", Json::from(column.as_str()), to_json(value)))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+fn to_json(value: &Value) -> Json {
+    match value {
+        Value::Boolean(b) => Json::Bool(*b),
+        Value::Integer(i) => Json::from(*i),
+        Value::Float(f) => Number::from_f64(f.0).map_or(Json::Null, Json::Number),
+        Value::String(s) => Json::from(s.as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Identifier, lexer::Span};
+
+    fn schema() -> RelationSchema {
+        let span = Span { start_offset: 0, end_offset: 1, start_line: 1, start_column: 1, end_line: 1, end_column: 1 };
+        RelationSchema {
+            name: Identifier::new("Item", span),
+            arity: 4,
+            columns: vec!["name".to_string(), "count".to_string(), "price".to_string(), "sold".to_string()],
+            types: vec![Some(Type::String), Some(Type::Integer), Some(Type::Float), Some(Type::Boolean)],
+            declared: true,
+        }
+    }
+
+    fn item(name: &str, count: i64, price: f64, sold: bool) -> Tuple {
+        vec![Value::String(name.to_string()), Value::Integer(count), Value::Float(Float(price)), Value::Boolean(sold)]
+    }
+
+    fn read(text: &str) -> Result<Vec<Tuple>, String> {
+        text.lines()
+            .map(|line| serde_json::from_str(line).map_err(|err| err.to_string()).and_then(|json| parse_tuple(json, &schema())))
+            .collect()
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let tuples = vec![
+            item("plain", 3, 1.5, true),
+            item("tab\tnew\nline \"quoted\" \\ é", -7, 2.0, false),
+            item("", 0, -0.25, true),
+        ];
+
+        let mut out = Vec::new();
+        Jsonl.write(&mut out, &schema(), &tuples).unwrap();
+        let text = String::from_utf8(out).unwrap();
+
+        assert_eq!(text.lines().next().unwrap(), r#"{"name":"plain","count":3,"price":1.5,"sold":true}"#);
+        assert_eq!(read(&text).unwrap(), tuples);
+    }
+
+    #[test]
+    fn reads_arrays_and_widens_integers_to_floats() {
+        assert_eq!(read(r#"["a", 1, 2, false]"#).unwrap(), vec![item("a", 1, 2.0, false)]);
+        assert_eq!(read(r#"{"sold": true, "price": 3, "count": 1, "name": "b", "extra": null}"#).unwrap(), vec![item("b", 1, 3.0, true)]);
+    }
+
+    #[test]
+    fn rejects_tuples_that_do_not_fit_the_schema() {
+        assert_eq!(read(r#"{"name": "a", "count": 1, "price": 2.0}"#).unwrap_err(), "Missing key 'sold'");
+        assert_eq!(read(r#"["a", 1, 2.0]"#).unwrap_err(), "Expected 4 columns but found 3");
+        assert_eq!(read(r#"["a", 1.5, 2.0, true]"#).unwrap_err(), "Column 2: Expected an integer but found 1.5");
+        assert_eq!(read(r#""a""#).unwrap_err(), "Expected an object or an array but found \"a\"");
+    }
+
+    #[test]
+    fn change_objects_read_back_as_the_tuple_epoch_and_diff() {
+        let changes = vec![
+            Change { tuple: item("a", 1, 0.5, true), epoch: 0, diff: 1 },
+            Change { tuple: item("b", 2, 1.0, false), epoch: 4, diff: -1 },
+        ];
+        let mut out = Vec::new();
+        JsonlChanges.write_changes(&mut out, &schema(), &changes).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text.lines().nth(1).unwrap(),
+            r#"{"tuple":{"name":"b","count":2,"price":1.0,"sold":false},"epoch":4,"diff":-1}"#
+        );
+
+        let read: Vec<Change> = text.lines()
+            .map(|line| {
+                let Json::Object(mut object) = serde_json::from_str(line).unwrap() else {
+                    panic!("Expected an object in '{}'", line);
+                };
+                Change {
+                    tuple: parse_tuple(object.remove("tuple").unwrap(), &schema()).unwrap(),
+                    epoch: object["epoch"].as_u64().unwrap(),
+                    diff: object["diff"].as_i64().unwrap() as isize,
+                }
+            })
+            .collect();
+        assert_eq!(read, changes);
+    }
+}
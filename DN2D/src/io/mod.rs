@@ -0,0 +1,10 @@
+pub mod csv;
+pub mod delta;
+pub mod format;
+pub mod jsonl;
+pub mod io_error;
+pub mod table;
+pub mod txt;
+
+pub use format::{ChangeReader, ChangeWriter, FormatRegistry, Reader, Writer};
+pub use io_error::IoError;
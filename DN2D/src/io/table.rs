@@ -0,0 +1,59 @@
+use std::io::Write;
+
+use crate::{
+    backend::{Tuple, Value},
+    io::Writer,
+    semantic::RelationSchema,
+};
+
+/// A box-drawn table with a header row, for reading small results in a
+/// terminal. Numbers are aligned to the right and everything else to the
+/// left; rows keep the sorted order in which tuples are written.
+pub struct Table;
+
+impl Writer for Table {
+    fn write(&self, out: &mut dyn Write, schema: &RelationSchema, tuples: &[Tuple]) -> std::io::Result<()> {
+        let rows: Vec<Vec<String>> = tuples.iter()
+            .map(|tuple| tuple.iter().map(Value::to_string).collect())
+            .collect();
+
+        let mut widths: Vec<usize> = schema.columns.iter().map(|column| column.chars().count()).collect();
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let right_aligned: Vec<bool> = (0..schema.arity)
+            .map(|column| !tuples.is_empty() && tuples.iter()
+                .all(|tuple| matches!(tuple[column], Value::Integer(_) | Value::Float(_))))
+            .collect();
+
+        writeln!(out, "{}", schema.name)?;
+        writeln!(out, "{}", border(&widths, '┌', '┬', '┐'))?;
+        writeln!(out, "{}", line(&widths, &schema.columns, &vec![false; schema.arity]))?;
+        writeln!(out, "{}", border(&widths, '├', '┼', '┤'))?;
+        for row in &rows {
+            writeln!(out, "{}", line(&widths, row, &right_aligned))?;
+        }
+        writeln!(out, "{}", border(&widths, '└', '┴', '┘'))
+    }
+}
+
+fn border(widths: &[usize], left: char, middle: char, right: char) -> String {
+    let segments: Vec<String> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+    format!("{}{}{}", left, segments.join(&middle.to_string()), right)
+}
+
+fn line(widths: &[usize], cells: &[String], right_aligned: &[bool]) -> String {
+    let cells: Vec<String> = cells.iter()
+        .zip(widths)
+        .zip(right_aligned)
+        .map(|((cell, &width), &right)| if right {
+            format!(" {:>width$} ", cell)
+        } else {
+            format!(" {:<width$} ", cell)
+        })
+        .collect();
+    format!("│{}│", cells.join("│"))
+}
@@ -0,0 +1,55 @@
+use std::io::Write;
+
+use crate::{
+    backend::{Tuple, Value},
+    io::Writer,
+    semantic::RelationSchema,
+};
+
+/// Plain text, one tuple per line with its values separated by tabs and
+/// strings written without quotes. Backslashes, tabs and newlines in strings
+/// are escaped as `\\`, `\t` and `\n` so that every tuple stays on one line.
+pub struct Txt;
+
+impl Writer for Txt {
+    fn write(&self, out: &mut dyn Write, _schema: &RelationSchema, tuples: &[Tuple]) -> std::io::Result<()> {
+        for tuple in tuples {
+            let values: Vec<String> = tuple.iter().map(field).collect();
+            writeln!(out, "{}", values.join("\t"))?;
+        }
+        Ok(())
+    }
+}
+
+fn field(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n"),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Identifier, lexer::Span, semantic::Type};
+
+    #[test]
+    fn tabs_newlines_and_backslashes_in_strings_are_escaped() {
+        let span = Span { start_offset: 0, end_offset: 1, start_line: 1, start_column: 1, end_line: 1, end_column: 1 };
+        let schema = RelationSchema {
+            name: Identifier::new("Note", span),
+            arity: 2,
+            columns: vec!["id".to_string(), "text".to_string()],
+            types: vec![Some(Type::Integer), Some(Type::String)],
+            declared: true,
+        };
+        let tuples = vec![
+            vec![Value::Integer(1), Value::String("a\tb\nc\\d".to_string())],
+            vec![Value::Integer(2), Value::String("plain".to_string())],
+        ];
+
+        let mut out = Vec::new();
+        Txt.write(&mut out, &schema, &tuples).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1\ta\\tb\\nc\\\\d\n2\tplain\n");
+    }
+}
@@ -1,13 +1,17 @@
 use std::{iter::Peekable, str::Chars};
 
-use crate::lexer::{LexerError, Span, Token, TokenKind};
+use crate::{diagnostics::source_line, lexer::{LexerError, Span, Token, TokenKind}};
 
 pub struct Lexer<'a> {
     source: &'a str,
     chars: Peekable<Chars<'a>>,
-    pos: usize,
+    /// The byte offset, line and column of the next character.
+    offset: usize,
     line: usize,
-    line_start_pos: usize,
+    column: usize,
+    /// The line and column of the last character read.
+    last_line: usize,
+    last_column: usize,
 }
 
 impl<'a> Lexer<'a> {
@@ -15,21 +19,39 @@ impl<'a> Lexer<'a> {
         Lexer {
             source,
             chars: source.chars().peekable(),
-            pos: 0,
+            offset: 0,
             line: 1,
-            line_start_pos: 0,
+            column: 1,
+            last_line: 1,
+            last_column: 0,
         }
     }
 
+    /// Scans the whole source. Each lexical error is reported and stands as an
+    /// `Illegal` token with its span, and scanning resumes after it, so that
+    /// every error of the source is found in a single pass.
+    pub fn tokenize(self) -> (Vec<Token>, Vec<LexerError>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(e) => {
+                    tokens.push(Token::new(TokenKind::Illegal, e.span));
+                    errors.push(e);
+                }
+            }
+        }
+        (tokens, errors)
+    }
+
     fn next_token(&mut self) -> Result<Token, LexerError> {
         self.skip_whitespace_and_comments();
 
-        let start_col = self.column();
-        let start_pos = self.position();
+        let start = self.here();
 
         let Some(ch) = self.next_char() else {
-            let span = Span::new(self.line, start_col, start_col);
-            return Ok(Token::new(TokenKind::Eof, span));
+            return Ok(Token::new(TokenKind::Eof, start));
         };
 
         let kind = match ch {
@@ -49,6 +71,13 @@ impl<'a> Lexer<'a> {
                     self.next_char();
                     TokenKind::ColonDash
                 },
+                _ => TokenKind::Colon,
+            },
+            '?' => match self.chars.peek() {
+                Some('-') => {
+                    self.next_char();
+                    TokenKind::QueryDash
+                },
                 _ => TokenKind::Illegal,
             },
             '!' => match self.chars.peek() {
@@ -80,35 +109,62 @@ impl<'a> Lexer<'a> {
                 _ => TokenKind::Gt,
             },
             '.' => match self.chars.peek() {
-                Some(c) if c.is_alphabetic() => self.read_directive()?,
+                Some(c) if c.is_alphabetic() => self.read_directive(start)?,
                 _ => TokenKind::Dot,
             }
-            '"' => self.read_string()?,
-            c if c.is_digit(10) => self.read_number(c)?,
+            '"' => self.read_string(start)?,
+            c if c.is_ascii_digit() => self.read_number(c, start)?,
             c if c.is_alphabetic() => self.read_identifier_or_keyword(c),
             _ => TokenKind::Illegal,
         };
 
-        let span = Span::new(self.line, start_col, self.column() - 1);
+        let span = self.span_from(start);
         if kind == TokenKind::Illegal {
-            let message = format!("Unrecognized character '{}'", &self.source[start_pos..self.position()]);
-            Err(LexerError { message, span })
+            let message = format!("Unrecognized character '{}'", &self.source[span.start_offset..span.end_offset]);
+            Err(self.error(span, message))
         } else {
             Ok(Token::new(kind, span))
         }
     }
 
+    fn error(&self, span: Span, message: impl Into<String>) -> LexerError {
+        LexerError { message: message.into(), line_ref: source_line(self.source, span.start_line), span }
+    }
+
     fn next_char(&mut self) -> Option<char> {
-        self.pos += 1;
-        self.chars.next()
+        let c = self.chars.next()?;
+        self.last_line = self.line;
+        self.last_column = self.column;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
     }
 
-    fn position(&mut self) -> usize {
-        self.pos
+    /// An empty span at the next character.
+    fn here(&self) -> Span {
+        Span {
+            start_offset: self.offset,
+            end_offset: self.offset,
+            start_line: self.line,
+            start_column: self.column,
+            end_line: self.line,
+            end_column: self.column,
+        }
     }
 
-    fn column(&self) -> usize {
-        self.pos - self.line_start_pos + 1
+    /// The span from `start` up to the last character read.
+    fn span_from(&self, start: Span) -> Span {
+        Span {
+            end_offset: self.offset,
+            end_line: self.last_line,
+            end_column: self.last_column,
+            ..start
+        }
     }
 
     fn skip_whitespace_and_comments(&mut self) {
@@ -116,10 +172,6 @@ impl<'a> Lexer<'a> {
             match self.chars.peek() {
                 Some(&c) if c.is_whitespace() => {
                     self.next_char();
-                    if c == '\n' {
-                        self.line += 1;
-                        self.line_start_pos = self.position();
-                    }
                 }
                 Some(&'#') => {
                     self.next_char();
@@ -157,7 +209,7 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn read_directive(&mut self) -> Result<TokenKind, LexerError> {
+    fn read_directive(&mut self, start: Span) -> Result<TokenKind, LexerError> {
         
         // The mutable borrow on `self` for next_char() ends here (unwrap).
         let first_char = self.next_char().unwrap();
@@ -167,25 +219,23 @@ impl<'a> Lexer<'a> {
         
         match directive {
             TokenKind::Identifier(s) => match s.as_str() {
+                "decl" => Ok(TokenKind::Decl),
                 "read" => Ok(TokenKind::Read),
                 "write" => Ok(TokenKind::Write),
                 "iterate" => Ok(TokenKind::Iterate),
-                _ => {
-                    let span = Span::new(self.line, self.column() - s.len() - 1, self.column() - 1);
-                    Err(LexerError { message: format!("Unknown directive '.{}'", s), span, })
-                }
+                _ => Err(self.error(self.span_from(start), format!("Unknown directive '.{}'", s))),
             },
             _ => unreachable!(),
         }
     }
     
-    fn read_number(&mut self, first: char) -> Result<TokenKind, LexerError> {
+    fn read_number(&mut self, first: char, start: Span) -> Result<TokenKind, LexerError> {
 
         let mut num_str = String::new();
         num_str.push(first);
         
         while let Some(&c) = self.chars.peek() {
-            if c.is_digit(10) {
+            if c.is_ascii_digit() {
                 num_str.push(self.next_char().unwrap());
             } else {
                 break;
@@ -193,12 +243,12 @@ impl<'a> Lexer<'a> {
         }
         
         if let Some('.') = self.chars.peek() {
-            if let Some(next_c) = self.source.chars().nth(self.position() + 1) {
-                 if next_c.is_digit(10) {
+            if let Some(next_c) = self.source[self.offset + 1..].chars().next() {
+                 if next_c.is_ascii_digit() {
                     num_str.push(self.next_char().unwrap());
 
                     while let Some(&c) = self.chars.peek() {
-                        if c.is_digit(10) {
+                        if c.is_ascii_digit() {
                             num_str.push(self.next_char().unwrap());
                         } else {
                             break;
@@ -209,19 +259,19 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        Ok(TokenKind::Integer(num_str.parse().unwrap()))
+        num_str.parse().map(TokenKind::Integer).map_err(|_| {
+            self.error(self.span_from(start), format!("Integer literal '{}' is too large", num_str))
+        })
     }
 
-    fn read_string(&mut self) -> Result<TokenKind, LexerError> {
+    fn read_string(&mut self, start: Span) -> Result<TokenKind, LexerError> {
         let mut s = String::new();
-        let start_col = self.column() - 1;
         loop {
             match self.next_char() {
                 Some('"') => break,
                 Some(c) => s.push(c),
                 None => {
-                    let span = Span::new(self.line, start_col, self.column());
-                    return Err(LexerError { message: "Unterminated string literal".into(), span, });
+                    return Err(self.error(self.span_from(start), "Unterminated string literal"));
                 }
             }
         }
@@ -238,4 +288,60 @@ impl<'a> Iterator for Lexer<'a> {
             Err(e) => Some(Err(e)),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Position = (usize, usize);
+
+    /// The kind, byte offsets and start and end line and column of each token.
+    fn spans(source: &str) -> Vec<(TokenKind, Position, Position, Position)> {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty());
+        tokens.into_iter()
+            .map(|Token { kind, span }| (
+                kind,
+                (span.start_offset, span.end_offset),
+                (span.start_line, span.start_column),
+                (span.end_line, span.end_column),
+            ))
+            .collect()
+    }
+
+    #[test]
+    fn offsets_count_bytes_and_columns_count_characters() {
+        assert_eq!(spans("Straße(\"é🙂\", x)"), vec![
+            (TokenKind::Identifier("Straße".to_string()), (0, 7), (1, 1), (1, 6)),
+            (TokenKind::LParen, (7, 8), (1, 7), (1, 7)),
+            (TokenKind::String("é🙂".to_string()), (8, 16), (1, 8), (1, 11)),
+            (TokenKind::Comma, (16, 17), (1, 12), (1, 12)),
+            (TokenKind::Identifier("x".to_string()), (18, 19), (1, 14), (1, 14)),
+            (TokenKind::RParen, (19, 20), (1, 15), (1, 15)),
+        ]);
+    }
+
+    #[test]
+    fn columns_restart_on_each_line() {
+        assert_eq!(spans("# ç\n\"a\nü\" .")[..], [
+            (TokenKind::String("a\nü".to_string()), (5, 11), (2, 1), (3, 2)),
+            (TokenKind::Dot, (12, 13), (3, 4), (3, 4)),
+        ]);
+    }
+
+    #[test]
+    fn an_illegal_character_is_underlined_by_its_column() {
+        let source = "P(\"ü\") → Q.";
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(tokens.iter().filter(|token| token.kind == TokenKind::Illegal).count(), 1);
+
+        let span = errors[0].span;
+        assert_eq!((span.start_offset, span.end_offset, span.start_column, span.end_column), (8, 11, 8, 8));
+        assert_eq!(
+            errors[0].diagnostic().to_string(),
+            "error[E0001]: Unrecognized character '→'\n --> 1:8\n  │\n1 │ P(\"ü\") → Q.\n  │        ┻"
+        );
+    }
+}
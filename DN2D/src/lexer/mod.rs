@@ -1,5 +1,6 @@
 pub mod span;
 pub mod token;
+#[allow(clippy::module_inception)]
 pub mod lexer;
 
 pub use span::Span;
@@ -1,14 +1,44 @@
 use serde::Serialize;
 
+/// A region of the source, which may cover several lines. Lines and columns
+/// are 1-based and count characters; the end line and column are those of the
+/// last character of the region.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct Span {
-    pub line: usize,
-    pub start: usize,
-    pub end: usize,
+    /// The byte offset of the first character.
+    pub start_offset: usize,
+    /// The byte offset just past the last character.
+    pub end_offset: usize,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
 }
 
 impl Span {
-    pub fn new(line: usize, start: usize, end: usize) -> Self {
-        Span { line, start, end }
+    /// The region from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end_offset: other.end_offset,
+            end_line: other.end_line,
+            end_column: other.end_column,
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_joins_spans_across_lines_of_non_ascii_text() {
+        // `Ü(` on line 1 and `ß)` on line 2 of "Ü(\nß)".
+        let start = Span { start_offset: 0, end_offset: 3, start_line: 1, start_column: 1, end_line: 1, end_column: 2 };
+        let end = Span { start_offset: 4, end_offset: 7, start_line: 2, start_column: 1, end_line: 2, end_column: 2 };
+        assert_eq!(
+            start.to(end),
+            Span { start_offset: 0, end_offset: 7, start_line: 1, start_column: 1, end_line: 2, end_column: 2 }
+        );
     }
 }
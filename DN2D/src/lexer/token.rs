@@ -2,14 +2,17 @@ use std::fmt;
 
 use serde::Serialize;
 
+use crate::diagnostics::{Code, Diagnostic, Label};
+
 use super::span::Span;
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum TokenKind {
+    Decl, Colon,
     Read, From,
     Write, To, As,
     Iterate,
-    LParen, RParen, LBrace, RBrace, Comma, Dot, ColonDash, Wildcard,
+    LParen, RParen, LBrace, RBrace, Comma, Dot, ColonDash, QueryDash, Wildcard,
     Not, Eq, NotEq, Lt, LtEq, Gt, GtEq, Plus, Minus, Star, Slash, Percent, Bang,
     Identifier(String),
     Integer(i64),
@@ -35,16 +38,26 @@ impl Token {
 #[derive(Debug, Clone, PartialEq)]
 pub struct LexerError {
     pub message: String,
+    pub line_ref: String,
     pub span: Span,
 }
 
+impl LexerError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(Code::LexicalError, self.message.clone());
+        diagnostic.labels.push(Label {
+            span: self.span,
+            message: String::new(),
+            line_ref: self.line_ref.clone(),
+            primary: true,
+        });
+        diagnostic
+    }
+}
+
 impl fmt::Display for LexerError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Lexer Error at Line {}, Col {}-{}: {}",
-            self.span.line, self.span.start, self.span.end, self.message
-        )
+        write!(f, "{}", self.diagnostic())
     }
 }
 
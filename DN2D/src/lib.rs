@@ -0,0 +1,12 @@
+//! DN2D compiles Datalog programs with negation into differential dataflows.
+//!
+//! The `dn2d` binary is a thin driver over these modules; programs embedding
+//! DN2D can use them directly, e.g. to register their own input and output
+//! formats in an [`io::FormatRegistry`].
+
+pub mod ast;
+pub mod diagnostics;
+pub mod io;
+pub mod lexer;
+pub mod backend;
+pub mod semantic;
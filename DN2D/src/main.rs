@@ -1,58 +1,143 @@
+use std::path::Path;
 use std::process;
 use std::fs;
 
-// Declare the modules
-mod ast;
 mod cli;
-mod lexer;
 
 // Bring items into scope
-use ast::Parser;
-use lexer::Lexer;
-use crate::cli::Command;
+use dn2d::ast::Parser;
+use dn2d::lexer::Lexer;
+use crate::cli::{Command, Mode};
 
-use crate::ast::Parsable;
-use crate::ast::Program;
-use crate::lexer::Token;
+use dn2d::ast::{ParserError, Program};
+use dn2d::diagnostics::{Code, Diagnostic, DiagnosticFormat};
+use dn2d::lexer::{LexerError, Token};
+use dn2d::io::FormatRegistry;
+use dn2d::semantic::{check_aggregates, check_formats, check_recursion, check_safety, infer_types, SemanticError, Stratification, SymbolTable};
+use dn2d::backend::{self, Engine, Semantics};
 
 fn main() {
     let cli = Command::new();
 
-    let filename = cli.src_path
+    let src_path = cli.src_path();
+    let filename = src_path
         .to_str()
         .unwrap_or_default()
         .to_string();
 
-    let source_code = fs::read_to_string(filename.clone()).unwrap_or_else(|err| {
-        eprintln!("Error: Could not read file '{}': {}", filename, err);
-        process::exit(1);
-    });
+    let report = Report { format: cli.diagnostics_format, filename: &filename };
+
+    let source_code = fs::read_to_string(filename.clone()).unwrap_or_else(|err| report.fail(vec![
+        Diagnostic::error(Code::IoError, format!("Could not read file '{}': {}", filename, err))
+    ]));
 
-    let tokens = lex(filename, &source_code);
+    let (tokens, lexer_errors) = lex(&filename, &source_code);
     cli.lex_as_json.handle(cli::export_to::to_json_str(&tokens));
 
     let mut parser = Parser::new(&source_code, tokens);
-    let program_ast = match Program::parse(&mut parser) {
-        Ok(ast) => ast,
-        Err(e) => panic!("{}", e)
-    };
+    let (program_ast, parser_errors) = Program::parse_all(&mut parser);
     cli.ast_as_json.handle(cli::export_to::to_json_str(&program_ast));
-}
 
-fn lex(filename :String, source_code: &String) -> Vec<Token>{
+    // Report the lexical and syntax errors together.
+    let diagnostics: Vec<Diagnostic> = lexer_errors.iter()
+        .map(LexerError::diagnostic)
+        .chain(parser_errors.iter().map(ParserError::diagnostic))
+        .collect();
+    if !diagnostics.is_empty() {
+        report.fail(diagnostics);
+    }
 
-    println!("--- Lexing file: {} ---", filename);
+    let mut symbols = SymbolTable::new(&program_ast, &source_code).unwrap_or_else(|e| report.fail_semantic(e));
 
-    let lexer = Lexer::new(&source_code);
-    let tokens: Vec<_> = match lexer.collect::<Result<_, _>>() {
-        Ok(t) => t,
-        Err(e) => {
-            eprintln!("{}", e);
-            process::exit(1);
+    if cli.strict {
+        if let Err(e) = symbols.require_declarations(&source_code) {
+            report.fail_semantic(e);
         }
+    }
+
+    if let Err(e) = check_aggregates(&program_ast, &source_code) {
+        report.fail_semantic(e);
+    }
+
+    if let Err(e) = infer_types(&program_ast, &mut symbols, &source_code) {
+        report.fail_semantic(e);
+    }
+
+    let formats = FormatRegistry::default();
+    if let Err(e) = check_formats(&program_ast, &formats, &source_code) {
+        report.fail_semantic(e);
+    }
+
+    if let Err(e) = check_safety(&program_ast, &source_code) {
+        report.fail_semantic(e);
+    }
+
+    let engine = match cli.semantics {
+        Semantics::Stratified => {
+            let stratification = Stratification::new(&program_ast, &source_code).unwrap_or_else(|e| report.fail_semantic(e));
+            Engine::new(&program_ast, &symbols, &stratification)
+        }
+        Semantics::WellFounded => {
+            check_recursion(&program_ast, &source_code).unwrap_or_else(|e| report.fail_semantic(e));
+            Engine::well_founded(&program_ast, &symbols)
+        }
+    };
+    let engine = engine.unwrap_or_else(|e| report.fail(vec![e.diagnostic()]));
+
+    let base_dir = src_path.parent().unwrap_or(Path::new("."));
+    let result = if matches!(cli.mode, Some(Mode::Repl { .. })) {
+        backend::repl(&program_ast, engine, &symbols, &formats, base_dir)
+    } else if cli.watch {
+        backend::watch(&program_ast, engine, &symbols, &formats, base_dir)
+    } else {
+        backend::run(&program_ast, engine, &symbols, &formats, base_dir)
     };
+    if let Err(e) = result {
+        report.fail(vec![e.diagnostic()]);
+    }
+}
+
+/// Where and how the errors found in the program are reported.
+struct Report<'a> {
+    format: DiagnosticFormat,
+    filename: &'a str,
+}
+
+impl Report<'_> {
+    /// Prints `diagnostics` in source order and exits.
+    fn fail(&self, mut diagnostics: Vec<Diagnostic>) -> ! {
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span().map(|span| span.start_offset));
+        let count = diagnostics.len();
+        for diagnostic in diagnostics {
+            let diagnostic = diagnostic.in_file(self.filename);
+            match self.format {
+                DiagnosticFormat::Human => eprintln!("{}\n", diagnostic),
+                DiagnosticFormat::Json => eprintln!("{}", diagnostic.to_json()),
+            }
+        }
+        if self.format == DiagnosticFormat::Human {
+            let plural = if count == 1 { "" } else { "s" };
+            eprintln!("Found {} error{}.", count, plural);
+        }
+        process::exit(1);
+    }
+
+    fn fail_semantic(&self, errors: Vec<SemanticError>) -> ! {
+        self.fail(errors.iter().map(SemanticError::diagnostic).collect())
+    }
+}
+
+fn lex(filename: &str, source_code: &str) -> (Vec<Token>, Vec<LexerError>) {
+
+    eprintln!("--- Lexing file: {} ---", filename);
+
+    let (tokens, errors) = Lexer::new(source_code).tokenize();
 
-    println!("Lexing successful. Found {} tokens.", tokens.len());
+    if errors.is_empty() {
+        eprintln!("Lexing successful. Found {} tokens.", tokens.len());
+    } else {
+        eprintln!("Lexing failed. Found {} tokens and {} illegal ones.", tokens.len() - errors.len(), errors.len());
+    }
 
-    tokens
+    (tokens, errors)
 }
@@ -0,0 +1,67 @@
+use crate::{
+    ast::{Aggregate, Expression, Literal, Program},
+    diagnostics::Code,
+    semantic::SemanticError,
+};
+
+/// Checks that aggregates are only used as whole terms of rule heads, where
+/// they are computed over the bindings of the rule body: not within another
+/// expression or aggregate, and not in facts, rule bodies or queries. Reports
+/// the first misplaced aggregate of each fact, rule and query.
+pub fn check_aggregates(program: &Program, source: &str) -> Result<(), Vec<SemanticError>> {
+    let mut errors = Vec::new();
+
+    for fact in program.facts() {
+        if let Some(aggregate) = fact.head.terms.iter().find_map(find_aggregate) {
+            errors.push(misplaced(aggregate, source, "in a fact"));
+        }
+    }
+
+    for rule in program.rules() {
+        let in_head = rule.head.terms.iter().find_map(|term| match term {
+            Expression::Aggregate(aggregate) => aggregate.arg.as_deref()
+                .and_then(find_aggregate)
+                .map(|nested| misplaced(nested, source, "inside another aggregate")),
+            term => find_aggregate(term).map(|nested| misplaced(nested, source, "within an expression")),
+        });
+        errors.extend(in_head.or_else(|| in_body(&rule.body, source, "in a rule body")));
+    }
+
+    for query in program.queries() {
+        errors.extend(in_body(&query.body, source, "in a query"));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn in_body(body: &[Literal], source: &str, place: &str) -> Option<SemanticError> {
+    body.iter()
+        .find_map(|literal| match literal {
+            Literal::Positive(atom) | Literal::Negative(atom) => atom.terms.iter().find_map(find_aggregate),
+            Literal::Condition(condition) => find_aggregate(condition),
+        })
+        .map(|aggregate| misplaced(aggregate, source, place))
+}
+
+/// The first aggregate within `expr`, or `expr` itself if it is one.
+fn find_aggregate(expr: &Expression) -> Option<&Aggregate> {
+    match expr {
+        Expression::Aggregate(aggregate) => Some(aggregate),
+        Expression::Binary { left, right, .. } => find_aggregate(left).or_else(|| find_aggregate(right)),
+        Expression::Unary { expr, .. } | Expression::Paren(expr, _) => find_aggregate(expr),
+        Expression::Constant(..) | Expression::Variable(_) | Expression::Wildcard(_) => None,
+    }
+}
+
+fn misplaced(aggregate: &Aggregate, source: &str, place: &str) -> SemanticError {
+    SemanticError::new(
+        Code::MisplacedAggregate,
+        source,
+        aggregate.span,
+        format!("Aggregate '{}' cannot be used {}", aggregate.func, place)
+    ).with_help("Aggregates can only be whole terms of a rule head; compute this one in a rule of its own and use the relation it derives")
+}
@@ -0,0 +1,38 @@
+use crate::{
+    ast::{Program, Statement},
+    diagnostics::Code,
+    io::FormatRegistry,
+    semantic::SemanticError,
+};
+
+/// Checks that the format of every `.read` and `.write` directive has a reader
+/// or writer in `formats`.
+pub fn check_formats(program: &Program, formats: &FormatRegistry, source: &str) -> Result<(), Vec<SemanticError>> {
+    let mut errors = Vec::new();
+    for statement in &program.statements {
+        let (format, span, known, kind): (_, _, Vec<&str>, _) = match statement {
+            Statement::Read(read)
+                if formats.reader(&read.format).is_none() && formats.change_reader(&read.format).is_none() => {
+                (&read.format, read.format_span, formats.reader_formats().collect(), "input")
+            }
+            Statement::Write(write)
+                if formats.writer(&write.format).is_none() && formats.change_writer(&write.format).is_none() => {
+                (&write.format, write.format_span, formats.writer_formats().collect(), "output")
+            }
+            _ => continue,
+        };
+
+        errors.push(SemanticError::new(
+            Code::UnknownFormat,
+            source,
+            span,
+            format!("Unknown {} format '{}', expected one of: {}", kind, format, known.join(", "))
+        ));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
@@ -0,0 +1,15 @@
+pub mod stratifier;
+pub mod semantic_error;
+pub mod safety;
+pub mod symbol_table;
+pub mod types;
+pub mod formats;
+pub mod aggregates;
+
+pub use stratifier::{check_recursion, Stratification};
+pub use semantic_error::SemanticError;
+pub use safety::check_safety;
+pub use symbol_table::{RelationSchema, SymbolTable};
+pub use types::{infer_types, Type};
+pub use formats::check_formats;
+pub use aggregates::check_aggregates;
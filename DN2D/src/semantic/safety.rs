@@ -0,0 +1,255 @@
+use std::collections::HashSet;
+
+use crate::{
+    ast::{Expression, Fact, Identifier, Literal, Program, Rule},
+    diagnostics::Code,
+    lexer::Span,
+    semantic::SemanticError,
+};
+
+/// Checks that every rule is range-restricted: its body must have a positive
+/// atom, and each variable of its head, of its negated atoms and of its
+/// conditions must be bound by one, so that the rule only ever derives
+/// finitely many tuples. Wildcards bind nothing, so they may only be whole
+/// terms of body atoms, whose other terms must be variables or constant
+/// expressions. Queries are checked like the bodies of rules, and facts must
+/// be ground. Reports the first problem of each fact, rule and query.
+pub fn check_safety(program: &Program, source: &str) -> Result<(), Vec<SemanticError>> {
+    let errors: Vec<SemanticError> = program.facts()
+        .map(|fact| check_fact(fact, source))
+        .chain(program.rules().map(|rule| check_rule(rule, source)))
+        .chain(program.queries().map(|query| {
+            check_positive(&query.body, query.span, source, || "Query".to_string(), "query")?;
+            check_atom_terms(&query.body, source, "query")?;
+            check_body(&query.body, &bound_by(&query.body), source, "query")
+        }))
+        .filter_map(Result::err)
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Checks that a fact has neither variables nor wildcards, which nothing binds.
+fn check_fact(fact: &Fact, source: &str) -> Result<(), SemanticError> {
+    for term in &fact.head.terms {
+        if let Some(variable) = term.variables().first() {
+            return Err(SemanticError::new(
+                Code::UnboundVariable,
+                source,
+                variable.span,
+                format!("Variable '{}' in the fact '{}' is not bound", variable, fact.head.name)
+            ).with_help("Facts can only contain constants; use a rule to bind variables"));
+        }
+        if let Some(span) = wildcard(term) {
+            return Err(SemanticError::new(
+                Code::UnboundVariable,
+                source,
+                span,
+                format!("The fact '{}' cannot contain the wildcard '_'", fact.head.name)
+            ).with_help("Facts can only contain constants"));
+        }
+    }
+    Ok(())
+}
+
+fn check_rule(rule: &Rule, source: &str) -> Result<(), SemanticError> {
+    check_positive(&rule.body, rule.head.span, source, || format!("Rule for '{}'", rule.head.name), "rule body")?;
+    check_atom_terms(&rule.body, source, "rule body")?;
+    let bound = bound_by(&rule.body);
+
+    let head = || format!("the head of '{}'", rule.head.name);
+    check_wildcards(&rule.head.terms, source, head)?;
+    check_bound(&rule.head.terms, &bound, source, "rule body", head)?;
+    check_body(&rule.body, &bound, source, "rule body")
+}
+
+/// Checks that `body` has a positive atom, reporting its absence at `span`.
+fn check_positive(
+    body: &[Literal],
+    span: Span,
+    source: &str,
+    subject: impl FnOnce() -> String,
+    scope: &str,
+) -> Result<(), SemanticError> {
+    if body.iter().any(|literal| matches!(literal, Literal::Positive(_))) {
+        return Ok(());
+    }
+    Err(SemanticError::new(
+        Code::UnboundVariable,
+        source,
+        span,
+        format!("{} has no positive atom in its body", subject())
+    ).with_help(format!("Add a positive atom to the {}", scope)))
+}
+
+/// The variables of the positive atoms of `body`.
+fn bound_by(body: &[Literal]) -> HashSet<&Identifier> {
+    body.iter()
+        .filter_map(|literal| match literal {
+            Literal::Positive(atom) => Some(atom),
+            _ => None,
+        })
+        .flat_map(|atom| &atom.terms)
+        .filter_map(|term| match term {
+            Expression::Variable(name) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+fn check_body(body: &[Literal], bound: &HashSet<&Identifier>, source: &str, scope: &str) -> Result<(), SemanticError> {
+    for literal in body {
+        match literal {
+            Literal::Positive(_) => {}
+            Literal::Negative(atom) => {
+                check_bound(&atom.terms, bound, source, scope, || format!("the negated atom 'not {}'", atom.name))?;
+            }
+            Literal::Condition(condition) => {
+                check_wildcards([condition], source, || "a condition".to_string())?;
+                check_bound([condition], bound, source, scope, || "a condition".to_string())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reports the first variable of `terms` that is not in `bound`.
+fn check_bound<'a>(
+    terms: impl IntoIterator<Item = &'a Expression>,
+    bound: &HashSet<&Identifier>,
+    source: &str,
+    scope: &str,
+    context: impl FnOnce() -> String,
+) -> Result<(), SemanticError> {
+    let unbound = terms.into_iter()
+        .flat_map(|term| term.variables())
+        .find(|variable| !bound.contains(variable));
+
+    match unbound {
+        Some(variable) => Err(SemanticError::new(
+            Code::UnboundVariable,
+            source,
+            variable.span,
+            format!("Variable '{}' in {} is not bound by a positive atom of the {}", variable, context(), scope)
+        ).with_help(format!("Add a positive atom that binds '{}' to the {}", variable, scope))),
+        None => Ok(()),
+    }
+}
+
+/// Checks that every term of the atoms of `body` is a variable, a wildcard or
+/// an expression without either, as only those can be matched against tuples.
+fn check_atom_terms(body: &[Literal], source: &str, scope: &str) -> Result<(), SemanticError> {
+    let atoms = body.iter().filter_map(|literal| match literal {
+        Literal::Positive(atom) | Literal::Negative(atom) => Some(atom),
+        Literal::Condition(_) => None,
+    });
+    for atom in atoms {
+        for term in &atom.terms {
+            if matches!(term, Expression::Variable(_) | Expression::Wildcard(_)) {
+                continue;
+            }
+            if !term.variables().is_empty() || wildcard(term).is_some() {
+                return Err(SemanticError::new(
+                    Code::ComputedAtomTerm,
+                    source,
+                    term.span(),
+                    format!("Atom '{}' in a {} can only contain variables, constants and wildcards", atom.name, scope)
+                ).with_help("Bind the value to a new variable and compare the two in a condition"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reports the first wildcard of `terms`.
+fn check_wildcards<'a>(
+    terms: impl IntoIterator<Item = &'a Expression>,
+    source: &str,
+    context: impl FnOnce() -> String,
+) -> Result<(), SemanticError> {
+    match terms.into_iter().find_map(wildcard) {
+        Some(span) => Err(SemanticError::new(
+            Code::UnboundVariable,
+            source,
+            span,
+            format!("The wildcard '_' in {} is not bound", context())
+        ).with_help("Wildcards can only be terms of atoms in the body; use a variable instead")),
+        None => Ok(()),
+    }
+}
+
+/// The span of the first wildcard in `expr`.
+fn wildcard(expr: &Expression) -> Option<Span> {
+    match expr {
+        Expression::Wildcard(span) => Some(*span),
+        Expression::Aggregate(aggregate) => aggregate.arg.as_deref().and_then(wildcard),
+        Expression::Binary { left, right, .. } => wildcard(left).or_else(|| wildcard(right)),
+        Expression::Unary { expr, .. } | Expression::Paren(expr, _) => wildcard(expr),
+        Expression::Constant(..) | Expression::Variable(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Parser, lexer::Lexer};
+
+    /// The code, line and column of each error of `source`.
+    fn check(source: &str) -> Vec<(Code, usize, usize)> {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty());
+        let (program, errors) = Program::parse_all(&mut Parser::new(source, tokens));
+        assert!(errors.is_empty());
+
+        check_safety(&program, source).err().unwrap_or_default().iter()
+            .map(|error| {
+                let diagnostic = error.diagnostic();
+                let span = diagnostic.span().unwrap();
+                (diagnostic.code, span.start_line, span.start_column)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn accepts_wildcards_and_constant_expressions_as_body_atom_terms() {
+        assert_eq!(check("E(1, 2).\nP(x) :- E(x, _), not E(_, 1 + 1).\n?- E(_, 2 * 3).\n"), vec![]);
+    }
+
+    #[test]
+    fn rejects_a_wildcard_in_the_head() {
+        assert_eq!(check("E(1).\nP(_) :- E(x).\nQ(x + _) :- E(x).\n"), vec![
+            (Code::UnboundVariable, 2, 3),
+            (Code::UnboundVariable, 3, 7),
+        ]);
+    }
+
+    #[test]
+    fn rejects_a_wildcard_in_a_condition() {
+        assert_eq!(check("E(1).\nP(x) :- E(x), _ > 1.\n?- E(x), x != -_.\n"), vec![
+            (Code::UnboundVariable, 2, 15),
+            (Code::UnboundVariable, 3, 16),
+        ]);
+    }
+
+    #[test]
+    fn rejects_a_computed_term_in_a_body_atom() {
+        assert_eq!(check("E(1).\nP(x) :- E(x), E(x + 1).\nQ(x) :- E(x), not E((_)).\n?- E(x), E(-x).\n"), vec![
+            (Code::ComputedAtomTerm, 2, 17),
+            (Code::ComputedAtomTerm, 3, 21),
+            (Code::ComputedAtomTerm, 4, 12),
+        ]);
+    }
+
+    #[test]
+    fn rejects_an_unbound_variable_where_it_is_used() {
+        assert_eq!(check("E(1).\nP(x, y) :- E(x).\nQ(x) :- E(x), not E(y).\nR(x) :- not E(x).\n"), vec![
+            (Code::UnboundVariable, 2, 6),
+            (Code::UnboundVariable, 3, 21),
+            (Code::UnboundVariable, 4, 1),
+        ]);
+    }
+}
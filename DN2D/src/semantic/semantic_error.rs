@@ -0,0 +1,35 @@
+use crate::{diagnostics::{Code, Diagnostic}, lexer::Span};
+
+#[derive(Debug)]
+pub struct SemanticError {
+    diagnostic: Diagnostic,
+}
+
+impl SemanticError {
+    pub fn new(code: Code, source: &str, span: Span, message: impl Into<String>) -> Self {
+        SemanticError {
+            diagnostic: Diagnostic::error(code, message).with_label(source, span, ""),
+        }
+    }
+
+    /// Points at a secondary location that helps explain the error.
+    pub fn with_note(self, source: &str, span: Span, message: impl Into<String>) -> Self {
+        SemanticError { diagnostic: self.diagnostic.with_secondary_label(source, span, message) }
+    }
+
+    pub fn with_help(self, message: impl Into<String>) -> Self {
+        SemanticError { diagnostic: self.diagnostic.with_help(message) }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        self.diagnostic.clone()
+    }
+}
+
+impl std::fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.diagnostic)
+    }
+}
+
+impl std::error::Error for SemanticError {}
@@ -0,0 +1,364 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::{
+    ast::{Expression, Identifier, Literal, Program, Rule, RuleOrFact, Statement},
+    diagnostics::Code,
+    semantic::SemanticError,
+};
+
+/// An edge of the predicate dependency graph: the head of a rule depends on a
+/// relation used in its body. Like a negated relation, a relation that a head
+/// aggregates over must be fully computed first, so the edge is negative too.
+struct Dependency<'a> {
+    head: &'a Identifier,
+    body: &'a Identifier,
+    negative: bool,
+    aggregated: bool,
+    iterated: bool,
+}
+
+/// A strongly connected component of the predicate dependency graph.
+#[derive(Debug)]
+pub struct Component {
+    pub relations: BTreeSet<Identifier>,
+    pub recursive: bool,
+}
+
+/// The components of the predicate dependency graph, ordered so that every
+/// component comes after the components it depends on.
+///
+/// A program is stratifiable when no component depends negatively on itself;
+/// every negated relation is then fully computed before the rules negating it.
+/// Building the stratification reports the first problem of each component.
+#[derive(Debug)]
+pub struct Stratification {
+    pub components: Vec<Component>,
+}
+
+impl Stratification {
+    pub fn new(program: &Program, source: &str) -> Result<Stratification, Vec<SemanticError>> {
+        let graph = DependencyGraph::new(program);
+        let mut components = Vec::new();
+        let mut errors = Vec::new();
+
+        for component in graph.components() {
+            let internal: Vec<&Dependency> = component.iter()
+                .flat_map(|relation| graph.dependencies_of(relation))
+                .filter(|dependency| component.contains(dependency.body))
+                .collect();
+
+            if let Some(negative) = internal.iter().find(|dependency| dependency.negative) {
+                errors.push(graph.negative_cycle(negative, &component, source));
+            } else if let Some(dependency) = internal.iter().find(|dependency| !dependency.iterated) {
+                errors.push(recursion_outside_iterate(dependency, source));
+            }
+
+            components.push(Component {
+                relations: component.into_iter().cloned().collect(),
+                recursive: !internal.is_empty(),
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(Stratification { components })
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The groups of mutually recursive relations, each of which has to be
+    /// evaluated to a fixpoint together.
+    pub fn recursive_components(&self) -> impl Iterator<Item = &BTreeSet<Identifier>> {
+        self.components.iter()
+            .filter(|component| component.recursive)
+            .map(|component| &component.relations)
+    }
+}
+
+/// Checks that every recursive rule is in an `.iterate` block, as
+/// [`Stratification::new`] does, for programs evaluated without a stratification.
+pub fn check_recursion(program: &Program, source: &str) -> Result<(), Vec<SemanticError>> {
+    let graph = DependencyGraph::new(program);
+    let errors: Vec<SemanticError> = graph.components()
+        .iter()
+        .filter_map(|component| component.iter()
+            .flat_map(|relation| graph.dependencies_of(relation))
+            .find(|dependency| component.contains(dependency.body) && !dependency.iterated))
+        .map(|dependency| recursion_outside_iterate(dependency, source))
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn recursion_outside_iterate(dependency: &Dependency, source: &str) -> SemanticError {
+    SemanticError::new(
+        Code::RecursionOutsideIterate,
+        source,
+        dependency.head.span,
+        format!("Relation '{}' is defined recursively outside of an .iterate block", dependency.head)
+    ).with_note(
+        source,
+        dependency.body.span,
+        format!("'{}' depends on '{}' here", dependency.head, dependency.body)
+    ).with_help(format!("Move the rules that define '{}' into an .iterate block", dependency.head))
+}
+
+struct DependencyGraph<'a> {
+    relations: BTreeSet<&'a Identifier>,
+    dependencies: BTreeMap<&'a Identifier, Vec<Dependency<'a>>>,
+}
+
+impl<'a> DependencyGraph<'a> {
+    fn new(program: &'a Program) -> Self {
+        let mut graph = DependencyGraph { relations: BTreeSet::new(), dependencies: BTreeMap::new() };
+
+        for statement in &program.statements {
+            match statement {
+                Statement::Rule(rule) => graph.add_rule(rule, false),
+                Statement::Iterate(block) => {
+                    for rule_or_fact in &block.rules {
+                        if let RuleOrFact::Rule(rule) = rule_or_fact {
+                            graph.add_rule(rule, true);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        graph
+    }
+
+    fn add_rule(&mut self, rule: &'a Rule, iterated: bool) {
+        let head = &rule.head.name;
+        let aggregated = rule.head.terms.iter().any(|term| matches!(term, Expression::Aggregate(_)));
+        self.relations.insert(head);
+
+        for literal in &rule.body {
+            let (atom, negated) = match literal {
+                Literal::Positive(atom) => (atom, false),
+                Literal::Negative(atom) => (atom, true),
+                Literal::Condition(_) => continue,
+            };
+            self.relations.insert(&atom.name);
+            self.dependencies.entry(head).or_default().push(Dependency {
+                head,
+                body: &atom.name,
+                negative: negated || aggregated,
+                aggregated: aggregated && !negated,
+                iterated,
+            });
+        }
+    }
+
+    fn dependencies_of(&self, relation: &Identifier) -> &[Dependency<'a>] {
+        self.dependencies.get(relation).map_or(&[], |dependencies| dependencies.as_slice())
+    }
+
+    /// Tarjan's algorithm; components are emitted after the components they depend on.
+    fn components(&self) -> Vec<BTreeSet<&'a Identifier>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: HashMap::new(),
+            low: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            components: Vec::new(),
+        };
+        for relation in &self.relations {
+            if !tarjan.index.contains_key(relation) {
+                tarjan.visit(relation);
+            }
+        }
+        tarjan.components
+    }
+
+    /// Reports the cycle closed by `negative`, found by a breadth-first search
+    /// from the negated relation back to the head of its rule.
+    fn negative_cycle(&self, negative: &Dependency<'a>, component: &BTreeSet<&'a Identifier>, source: &str) -> SemanticError {
+        let mut reached_by: HashMap<&Identifier, &Dependency> = HashMap::new();
+        let mut queue = VecDeque::from([negative.body]);
+
+        while let Some(relation) = queue.pop_front() {
+            if relation == negative.head {
+                break;
+            }
+            for dependency in self.dependencies_of(relation) {
+                if component.contains(dependency.body) && !reached_by.contains_key(dependency.body) {
+                    reached_by.insert(dependency.body, dependency);
+                    queue.push_back(dependency.body);
+                }
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut relation = negative.head;
+        while relation != negative.body {
+            let dependency = reached_by[relation];
+            path.push(dependency);
+            relation = dependency.head;
+        }
+        path.push(negative);
+        path.reverse();
+
+        let negated = |dependency: &Dependency| dependency.negative && !dependency.aggregated;
+        let mut chain = negative.head.name.clone();
+        for dependency in &path {
+            chain.push_str(if negated(dependency) { " -> not " } else { " -> " });
+            chain.push_str(&dependency.body.name);
+        }
+
+        let message = if negative.aggregated {
+            format!("Relation '{}' aggregates over itself through recursion ({})", negative.head, chain)
+        } else {
+            format!("Relation '{}' depends negatively on itself through recursion ({})", negative.head, chain)
+        };
+        let mut error = SemanticError::new(Code::NegativeCycle, source, negative.body.span, message);
+        // A relation negating itself directly is already pointed at above.
+        if path.len() > 1 {
+            for dependency in path {
+                let how = if dependency.aggregated {
+                    "aggregates over"
+                } else if dependency.negative {
+                    "depends on not"
+                } else {
+                    "depends on"
+                };
+                error = error.with_note(
+                    source,
+                    dependency.body.span,
+                    format!("'{}' {} '{}' here", dependency.head, how, dependency.body)
+                );
+            }
+        }
+        if negative.aggregated {
+            error.with_help(format!("Aggregate over relations that do not depend on '{}'", negative.head))
+        } else {
+            error.with_help("Run with '--semantics well-founded' to evaluate negation through recursion")
+        }
+    }
+}
+
+struct Tarjan<'g, 'a> {
+    graph: &'g DependencyGraph<'a>,
+    index: HashMap<&'a Identifier, usize>,
+    low: HashMap<&'a Identifier, usize>,
+    stack: Vec<&'a Identifier>,
+    on_stack: HashSet<&'a Identifier>,
+    components: Vec<BTreeSet<&'a Identifier>>,
+}
+
+impl<'a> Tarjan<'_, 'a> {
+    fn visit(&mut self, relation: &'a Identifier) {
+        let index = self.index.len();
+        self.index.insert(relation, index);
+        self.low.insert(relation, index);
+        self.stack.push(relation);
+        self.on_stack.insert(relation);
+
+        for dependency in self.graph.dependencies_of(relation) {
+            let body = dependency.body;
+            if !self.index.contains_key(body) {
+                self.visit(body);
+                let low = self.low[relation].min(self.low[body]);
+                self.low.insert(relation, low);
+            } else if self.on_stack.contains(body) {
+                let low = self.low[relation].min(self.index[body]);
+                self.low.insert(relation, low);
+            }
+        }
+
+        if self.low[relation] == self.index[relation] {
+            let mut component = BTreeSet::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                component.insert(member);
+                if member == relation {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Parser, diagnostics::Diagnostic, lexer::Lexer};
+
+    fn parse(source: &str) -> Program {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty());
+        let (program, errors) = Program::parse_all(&mut Parser::new(source, tokens));
+        assert!(errors.is_empty());
+        program
+    }
+
+    fn stratify(source: &str) -> Result<Stratification, Vec<Diagnostic>> {
+        Stratification::new(&parse(source), source)
+            .map_err(|errors| errors.iter().map(SemanticError::diagnostic).collect())
+    }
+
+    #[test]
+    fn orders_components_after_their_dependencies() {
+        let stratification = stratify(
+            "E(1, 2).\n.iterate {\n  P(x, y) :- E(x, y).\n  P(x, z) :- P(x, y), E(y, z).\n}\nQ(x) :- E(x, _), not P(x, x).\n"
+        ).unwrap();
+
+        let order: Vec<Vec<&str>> = stratification.components.iter()
+            .map(|component| component.relations.iter().map(|relation| relation.name.as_str()).collect())
+            .collect();
+        assert_eq!(order, [vec!["E"], vec!["P"], vec!["Q"]]);
+
+        let recursive: Vec<&BTreeSet<Identifier>> = stratification.recursive_components().collect();
+        assert_eq!(recursive.len(), 1);
+        assert!(recursive[0].iter().all(|relation| relation.name == "P"));
+    }
+
+    #[test]
+    fn negative_cycle_names_every_relation_of_the_cycle() {
+        let errors = stratify(".iterate {\n  P(x) :- E(x), not Q(x).\n  Q(x) :- E(x), R(x).\n  R(x) :- P(x).\n}\nE(1).\n")
+            .unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, Code::NegativeCycle);
+        assert_eq!(
+            errors[0].message,
+            "Relation 'P' depends negatively on itself through recursion (P -> not Q -> R -> P)"
+        );
+        assert_eq!(errors[0].span().unwrap().start_line, 2);
+        assert_eq!(errors[0].labels.len(), 3);
+    }
+
+    #[test]
+    fn negative_cycle_through_an_aggregate() {
+        let errors = stratify(".iterate {\n  C(x, count(y)) :- E(x, y).\n  E(x, n) :- C(x, n).\n}\nE(1, 5).\n")
+            .unwrap_err();
+
+        assert_eq!(errors[0].code, Code::NegativeCycle);
+        assert_eq!(errors[0].message, "Relation 'C' aggregates over itself through recursion (C -> E -> C)");
+    }
+
+    #[test]
+    fn every_component_is_checked() {
+        let errors = stratify("E(1).\nP(x) :- E(x), not P(x).\nQ(x) :- Q(x).\n").unwrap_err();
+
+        let codes: Vec<Code> = errors.iter().map(|error| error.code).collect();
+        assert_eq!(codes, [Code::NegativeCycle, Code::RecursionOutsideIterate]);
+    }
+
+    #[test]
+    fn check_recursion_ignores_negation() {
+        let source = "E(1).\n.iterate {\n  P(x) :- E(x), not P(x).\n}\n";
+        assert!(check_recursion(&parse(source), source).is_ok());
+
+        let source = "E(1).\nP(x) :- E(x), not P(x).\n";
+        let errors = check_recursion(&parse(source), source).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].diagnostic().code, Code::RecursionOutsideIterate);
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    ast::{Atom, Expression, Identifier, Literal, Program, RuleOrFact, Statement},
+    diagnostics::Code,
+    semantic::{SemanticError, Type},
+};
+
+/// What is known about a relation before evaluation.
+#[derive(Debug)]
+pub struct RelationSchema {
+    /// Where the relation first appears: its `.read` directive if it has one,
+    /// otherwise its first use.
+    pub name: Identifier,
+    pub arity: usize,
+    /// The name of each column, from the `.decl` or `.read` directive of the
+    /// relation, or else from the head of its first rule: a variable names its
+    /// column, an aggregate is named after its function, and any other column
+    /// is named `column1`, `column2`, ... by position.
+    pub columns: Vec<String>,
+    /// The type of each column, once inferred; `None` where nothing constrains it.
+    pub types: Vec<Option<Type>>,
+    /// Whether the relation has a `.decl` directive, which `name` then points at.
+    pub declared: bool,
+}
+
+/// Every relation of a program with its schema.
+///
+/// Building the table checks that each relation is always used with the same
+/// number of columns as its `.decl` or first use, that no `.decl` or `.read`
+/// directive names two columns the same, that a `.read` of a declared relation
+/// names its columns as the `.decl` does, and that every relation that is read
+/// from or written is declared or defined by a `.read` directive, a rule or a
+/// fact. It reports every violation it finds.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    pub relations: BTreeMap<Identifier, RelationSchema>,
+}
+
+/// How an atom uses its relation.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Usage {
+    Defined,
+    Used,
+}
+
+impl SymbolTable {
+    pub fn new(program: &Program, source: &str) -> Result<SymbolTable, Vec<SemanticError>> {
+        let mut table = SymbolTable::default();
+        let mut defined = BTreeSet::new();
+        let mut errors = Vec::new();
+
+        for statement in &program.statements {
+            if let Statement::Decl(decl) = statement {
+                errors.extend(duplicate_columns(decl.columns.iter().map(|column| &column.name), source));
+                if let Some(schema) = table.relations.get(&decl.name) {
+                    errors.push(SemanticError::new(
+                        Code::DuplicateDeclaration,
+                        source,
+                        decl.name.span,
+                        format!("Relation '{}' is declared more than once", decl.name)
+                    ).with_note(
+                        source,
+                        schema.name.span,
+                        format!("'{}' is first declared here", decl.name)
+                    ));
+                    continue;
+                }
+
+                table.relations.insert(decl.name.clone(), RelationSchema {
+                    name: decl.name.clone(),
+                    arity: decl.columns.len(),
+                    columns: decl.columns.iter().map(|column| column.name.name.clone()).collect(),
+                    types: decl.columns.iter().map(|column| Some(Type::from(column.ty))).collect(),
+                    declared: true,
+                });
+                defined.insert(&decl.name);
+            }
+        }
+
+        for statement in &program.statements {
+            if let Statement::Read(read) = statement {
+                errors.extend(duplicate_columns(&read.columns, source));
+                let arity = read.columns.len();
+                if let Err(e) = table.declare(&read.name, arity, source) {
+                    errors.push(e);
+                }
+                defined.insert(&read.name);
+
+                let schema = table.relations.get_mut(&read.name).unwrap();
+                if !schema.declared {
+                    schema.columns = read.columns.iter().map(|column| column.name.clone()).collect();
+                } else if schema.arity == arity {
+                    errors.extend(mismatched_columns(schema, &read.columns, source));
+                }
+            }
+        }
+
+        let mut atoms = Vec::new();
+        for statement in &program.statements {
+            match statement {
+                Statement::Rule(rule) => collect_rule(&rule.head, &rule.body, &mut atoms),
+                Statement::Fact(fact) => atoms.push((&fact.head, Usage::Defined)),
+                Statement::Iterate(block) => {
+                    for rule_or_fact in &block.rules {
+                        match rule_or_fact {
+                            RuleOrFact::Rule(rule) => collect_rule(&rule.head, &rule.body, &mut atoms),
+                            RuleOrFact::Fact(fact) => atoms.push((&fact.head, Usage::Defined)),
+                        }
+                    }
+                }
+                Statement::Query(query) => collect_body(&query.body, &mut atoms),
+                Statement::Decl(_) | Statement::Read(_) | Statement::Write(_) => {}
+            }
+        }
+
+        for (atom, usage) in &atoms {
+            if let Err(e) = table.declare(&atom.name, atom.terms.len(), source) {
+                errors.push(e);
+            }
+            if *usage == Usage::Defined {
+                defined.insert(&atom.name);
+            }
+        }
+
+        let mut named = BTreeSet::new();
+        for statement in &program.statements {
+            if let Statement::Read(read) = statement {
+                named.insert(&read.name);
+            }
+        }
+        for rule in program.rules() {
+            let schema = table.relations.get_mut(&rule.head.name).unwrap();
+            if !schema.declared && named.insert(&rule.head.name) {
+                schema.columns = head_columns(&rule.head);
+            }
+        }
+
+        // Each undefined relation is reported once, at its first use.
+        let mut undefined = BTreeSet::new();
+        for (atom, usage) in &atoms {
+            if *usage == Usage::Used && !defined.contains(&atom.name) && undefined.insert(&atom.name) {
+                errors.push(SemanticError::new(
+                    Code::UndefinedRelation,
+                    source,
+                    atom.name.span,
+                    format!("Relation '{}' is never declared or defined by a .read directive, a rule or a fact", atom.name)
+                ));
+            }
+        }
+
+        for statement in &program.statements {
+            if let Statement::Write(write) = statement {
+                if !defined.contains(&write.name) && undefined.insert(&write.name) {
+                    errors.push(SemanticError::new(
+                        Code::UndefinedRelation,
+                        source,
+                        write.name.span,
+                        format!("Relation '{}' is written but never declared or defined by a .read directive, a rule or a fact", write.name)
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(table)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Checks that every relation has a `.decl` directive, as strict mode requires.
+    pub fn require_declarations(&self, source: &str) -> Result<(), Vec<SemanticError>> {
+        let mut undeclared: Vec<&RelationSchema> = self.relations.values()
+            .filter(|schema| !schema.declared)
+            .collect();
+        undeclared.sort_by_key(|schema| schema.name.span.start_offset);
+
+        if undeclared.is_empty() {
+            return Ok(());
+        }
+        Err(undeclared.into_iter()
+            .map(|schema| SemanticError::new(
+                Code::UndeclaredRelation,
+                source,
+                schema.name.span,
+                format!("Relation '{}' is not declared with a .decl directive", schema.name)
+            ).with_help(format!("Declare its columns with '.decl {}(name: type, ...).'", schema.name)))
+            .collect())
+    }
+
+    /// Records that `name` is used with `arity` columns, which must agree with
+    /// any earlier use.
+    fn declare(&mut self, name: &Identifier, arity: usize, source: &str) -> Result<(), SemanticError> {
+        match self.relations.get(name) {
+            Some(schema) if schema.arity != arity => Err(SemanticError::new(
+                Code::ArityMismatch,
+                source,
+                name.span,
+                format!("Relation '{}' is used with {} here but has {}", name, columns(arity), columns(schema.arity))
+            ).with_note(
+                source,
+                schema.name.span,
+                format!("'{}' has {} here", name, columns(schema.arity))
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.relations.insert(name.clone(), RelationSchema {
+                    name: name.clone(),
+                    arity,
+                    columns: (1..=arity).map(|column| format!("column{}", column)).collect(),
+                    types: vec![None; arity],
+                    declared: false,
+                });
+                Ok(())
+            }
+        }
+    }
+}
+
+fn collect_rule<'a>(head: &'a Atom, body: &'a [Literal], atoms: &mut Vec<(&'a Atom, Usage)>) {
+    atoms.push((head, Usage::Defined));
+    collect_body(body, atoms);
+}
+
+fn collect_body<'a>(body: &'a [Literal], atoms: &mut Vec<(&'a Atom, Usage)>) {
+    for literal in body {
+        if let Literal::Positive(atom) | Literal::Negative(atom) = literal {
+            atoms.push((atom, Usage::Used));
+        }
+    }
+}
+
+/// Names the columns of a relation after the terms of a rule head, keeping the
+/// positional name of any column whose name would repeat an earlier one.
+fn head_columns(head: &Atom) -> Vec<String> {
+    let mut columns: Vec<String> = Vec::with_capacity(head.terms.len());
+    for (position, term) in head.terms.iter().enumerate() {
+        let name = match term {
+            Expression::Variable(variable) => Some(variable.name.clone()),
+            Expression::Aggregate(aggregate) => Some(aggregate.func.to_string()),
+            _ => None,
+        };
+        let name = name
+            .filter(|name| !columns.contains(name))
+            .unwrap_or_else(|| format!("column{}", position + 1));
+        columns.push(name);
+    }
+    columns
+}
+
+/// Reports each column name that repeats an earlier one of the same directive.
+fn duplicate_columns<'a>(names: impl IntoIterator<Item = &'a Identifier>, source: &str) -> Vec<SemanticError> {
+    let mut seen: Vec<&Identifier> = Vec::new();
+    let mut errors = Vec::new();
+    for name in names {
+        match seen.iter().find(|earlier| earlier.name == name.name) {
+            Some(earlier) => errors.push(SemanticError::new(
+                Code::DuplicateColumn,
+                source,
+                name.span,
+                format!("Column '{}' is named more than once", name)
+            ).with_note(source, earlier.span, format!("'{}' is first named here", name))),
+            None => seen.push(name),
+        }
+    }
+    errors
+}
+
+/// Reports the first column of a `.read` directive that is named differently
+/// from the same column of the relation's `.decl`.
+fn mismatched_columns(schema: &RelationSchema, columns: &[Identifier], source: &str) -> Option<SemanticError> {
+    let (position, (column, declared)) = columns.iter()
+        .zip(&schema.columns)
+        .enumerate()
+        .find(|(_, (column, declared))| column.name != **declared)?;
+
+    Some(SemanticError::new(
+        Code::ColumnMismatch,
+        source,
+        column.span,
+        format!("Column {} of '{}' is read as '{}' but declared as '{}'", position + 1, schema.name, column, declared)
+    ).with_note(
+        source,
+        schema.name.span,
+        format!("'{}' is declared here", schema.name)
+    ).with_help(format!("Name the columns of the .read directive as the .decl of '{}' does", schema.name)))
+}
+
+fn columns(arity: usize) -> String {
+    match arity {
+        1 => "1 column".to_string(),
+        n => format!("{} columns", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Parser, lexer::Lexer};
+
+    /// The code, line and column of each error of `source`.
+    fn check(source: &str) -> Vec<(Code, usize, usize)> {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty());
+        let (program, errors) = Program::parse_all(&mut Parser::new(source, tokens));
+        assert!(errors.is_empty());
+
+        SymbolTable::new(&program, source).err().unwrap_or_default().iter()
+            .map(|error| {
+                let diagnostic = error.diagnostic();
+                let span = diagnostic.span().unwrap();
+                (diagnostic.code, span.start_line, span.start_column)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rejects_repeated_column_names() {
+        assert_eq!(
+            check(".decl R(a: int, b: int, a: string).\n.read S(x, y, x) from \"s.jsonl\" as \"jsonl\".\nR(1, 2, \"c\").\n"),
+            vec![(Code::DuplicateColumn, 1, 25), (Code::DuplicateColumn, 2, 15)]
+        );
+    }
+
+    #[test]
+    fn a_read_of_a_declared_relation_names_the_declared_columns() {
+        let decl = ".decl R(a: int, b: int).\n";
+        assert_eq!(check(&format!("{}.read R(a, b) from \"r.csv\" as \"csv\".\n", decl)), vec![]);
+        assert_eq!(
+            check(&format!("{}.read R(a, y) from \"r.csv\" as \"csv\".\n", decl)),
+            vec![(Code::ColumnMismatch, 2, 12)]
+        );
+        assert_eq!(
+            check(&format!("{}.read R(b, a) from \"r.csv\" as \"csv\".\n", decl)),
+            vec![(Code::ColumnMismatch, 2, 9)]
+        );
+    }
+
+    #[test]
+    fn names_repeated_head_variables_by_position() {
+        let source = "E(1).\nP(x, x, count(x)) :- E(x).\n";
+        let (tokens, _) = Lexer::new(source).tokenize();
+        let (program, _) = Program::parse_all(&mut Parser::new(source, tokens));
+        let table = SymbolTable::new(&program, source).unwrap();
+
+        let schema = table.relations.values().find(|schema| schema.name.name == "P").unwrap();
+        assert_eq!(schema.columns, vec!["x", "column2", "count"]);
+    }
+}
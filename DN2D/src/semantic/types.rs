@@ -0,0 +1,581 @@
+use std::{collections::{btree_map::Entry, BTreeMap, HashMap}, fmt};
+
+use crate::{
+    ast::{AggregateFunction, Atom, BinaryOperator, ColumnType, Constant, Expression, Identifier, Literal, Program, Rule, Statement},
+    diagnostics::Code,
+    lexer::Span,
+    semantic::{SemanticError, SymbolTable},
+};
+
+/// The type of a relation column or an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Boolean,
+    Integer,
+    Float,
+    String,
+    /// An integer or a float, whichever the rest of the program requires.
+    Number,
+}
+
+impl Type {
+    /// The most specific type both `self` and `other` can be, if any. Integers
+    /// are promoted to floats, as they are when evaluating expressions.
+    fn meet(self, other: Type) -> Option<Type> {
+        match (self, other) {
+            (a, b) if a == b => Some(a),
+            (Type::Number, numeric @ (Type::Integer | Type::Float))
+            | (numeric @ (Type::Integer | Type::Float), Type::Number) => Some(numeric),
+            (Type::Integer, Type::Float) | (Type::Float, Type::Integer) => Some(Type::Float),
+            _ => None,
+        }
+    }
+
+    /// The name of the type with an indefinite article, e.g. "an integer".
+    pub fn with_article(self) -> &'static str {
+        match self {
+            Type::Boolean => "a boolean",
+            Type::Integer => "an integer",
+            Type::Float => "a float",
+            Type::String => "a string",
+            Type::Number => "a number",
+        }
+    }
+}
+
+impl From<&Constant> for Type {
+    fn from(constant: &Constant) -> Self {
+        match constant {
+            Constant::Integer(_) => Type::Integer,
+            Constant::Float(_) => Type::Float,
+            Constant::String(_) => Type::String,
+            Constant::Boolean(_) => Type::Boolean,
+        }
+    }
+}
+
+impl From<ColumnType> for Type {
+    fn from(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::Int => Type::Integer,
+            ColumnType::Float => Type::Float,
+            ColumnType::String => Type::String,
+            ColumnType::Bool => Type::Boolean,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Boolean => write!(f, "boolean"),
+            Type::Integer => write!(f, "integer"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "string"),
+            Type::Number => write!(f, "number"),
+        }
+    }
+}
+
+/// Infers the type of every relation column by unifying the types of the
+/// terms used for it across all rules and facts, and records them in `symbols`.
+/// Columns declared with `.decl` keep their declared type.
+///
+/// The constraints of the whole program are collected before any is solved,
+/// so that the types inferred do not depend on the order of the statements.
+/// Reports the first conflict, such as a string column compared with a number,
+/// in each fact, rule and query.
+pub fn infer_types(program: &Program, symbols: &mut SymbolTable, source: &str) -> Result<(), Vec<SemanticError>> {
+    let mut checker = TypeChecker {
+        source,
+        classes: Vec::new(),
+        columns: HashMap::new(),
+        constraints: Vec::new(),
+        statement: 0,
+    };
+
+    for statement in &program.statements {
+        if let Statement::Decl(decl) = statement {
+            let columns = decl.columns.iter()
+                .map(|column| {
+                    let class = checker.fresh(Some(Type::from(column.ty)), Some(column.ty_span));
+                    checker.classes[class].declared = true;
+                    class
+                })
+                .collect();
+            checker.columns.insert(decl.name.clone(), columns);
+        }
+    }
+
+    for fact in program.facts() {
+        checker.atom(&fact.head, &mut HashMap::new());
+        checker.statement += 1;
+    }
+    for rule in program.rules() {
+        checker.rule(rule);
+        checker.statement += 1;
+    }
+    for query in program.queries() {
+        checker.body(&query.body, &mut HashMap::new());
+        checker.statement += 1;
+    }
+
+    let errors = checker.solve();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    for (relation, schema) in symbols.relations.iter_mut() {
+        if let Some(columns) = checker.columns.get(relation).cloned() {
+            schema.types = columns.into_iter().map(|class| checker.type_of(class)).collect();
+        }
+    }
+    Ok(())
+}
+
+/// A set of expressions and columns known to share a type.
+struct Class {
+    parent: usize,
+    ty: Option<Type>,
+    /// Where the type of the class was first fixed.
+    origin: Option<Span>,
+    /// Whether the type comes from a `.decl`, in which case it cannot change.
+    declared: bool,
+}
+
+/// Builds the message of a conflict from the types of the two classes.
+type Describe<'a> = Box<dyn FnOnce(Type, Type) -> String + 'a>;
+
+/// What a fact, rule or query requires of the types of its expressions.
+struct Constraint<'a> {
+    /// The index of the fact, rule or query it comes from.
+    statement: usize,
+    span: Span,
+    kind: ConstraintKind<'a>,
+}
+
+enum ConstraintKind<'a> {
+    /// Both classes have the same type.
+    Same(usize, usize, Describe<'a>),
+    /// The operands of a comparison, which have the same type unless both are numbers.
+    Compare { op: BinaryOperator, left: usize, right: usize },
+    /// The operands and result of an arithmetic operator, whose result type
+    /// depends on those of the operands.
+    Arithmetic { op: BinaryOperator, left: usize, right: usize, result: usize },
+}
+
+/// Unification over classes of types, kept in a union-find forest.
+struct TypeChecker<'a> {
+    source: &'a str,
+    classes: Vec<Class>,
+    columns: HashMap<Identifier, Vec<usize>>,
+    constraints: Vec<Constraint<'a>>,
+    /// The index of the fact, rule or query being collected.
+    statement: usize,
+}
+
+impl<'a> TypeChecker<'a> {
+    fn fresh(&mut self, ty: Option<Type>, origin: Option<Span>) -> usize {
+        let class = self.classes.len();
+        self.classes.push(Class { parent: class, ty, origin: ty.and(origin), declared: false });
+        class
+    }
+
+    fn find(&mut self, class: usize) -> usize {
+        let parent = self.classes[class].parent;
+        if parent == class {
+            return class;
+        }
+        let root = self.find(parent);
+        self.classes[class].parent = root;
+        root
+    }
+
+    fn type_of(&mut self, class: usize) -> Option<Type> {
+        let root = self.find(class);
+        self.classes[root].ty
+    }
+
+    /// Requires `a` and `b` to have the same type, reporting a conflict at
+    /// `span` with the message `describe` builds from their types.
+    fn same(&mut self, a: usize, b: usize, span: Span, describe: impl FnOnce(Type, Type) -> String + 'a) {
+        self.push(span, ConstraintKind::Same(a, b, Box::new(describe)));
+    }
+
+    /// Requires `class` to have type `ty`, reporting a conflict at `span` with
+    /// the message `describe` builds from the type it has instead.
+    fn require(&mut self, class: usize, ty: Type, span: Span, describe: impl FnOnce(Type) -> String + 'a) {
+        let expected = self.fresh(Some(ty), None);
+        self.same(class, expected, span, |found, _| describe(found));
+    }
+
+    fn push(&mut self, span: Span, kind: ConstraintKind<'a>) {
+        self.constraints.push(Constraint { statement: self.statement, span, kind });
+    }
+
+    /// Solves the constraints collected, returning the first conflict of each
+    /// fact, rule and query.
+    ///
+    /// Equalities come first, as their outcome does not depend on their order.
+    /// Comparisons and arithmetic operators then wait until the types of
+    /// their operands are known; when none of those that are left can learn
+    /// anything more, the first is solved with what is known.
+    fn solve(&mut self) -> Vec<SemanticError> {
+        let mut errors = BTreeMap::new();
+        let (same, mut pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.constraints).into_iter()
+            .partition(|constraint| matches!(constraint.kind, ConstraintKind::Same(..)));
+
+        for constraint in same {
+            if let Entry::Vacant(entry) = errors.entry(constraint.statement) {
+                if let Err(error) = self.apply(constraint.kind, constraint.span) {
+                    entry.insert(error);
+                }
+            }
+        }
+
+        loop {
+            pending.retain(|constraint| !errors.contains_key(&constraint.statement));
+            if pending.is_empty() {
+                break;
+            }
+
+            let next = pending.iter().position(|constraint| self.ready(&constraint.kind)).unwrap_or(0);
+            let constraint = pending.remove(next);
+            if let Err(error) = self.apply(constraint.kind, constraint.span) {
+                errors.insert(constraint.statement, error);
+            }
+        }
+        errors.into_values().collect()
+    }
+
+    /// Whether the types of the operands of a constraint are known well
+    /// enough that solving it cannot depend on constraints solved later.
+    fn ready(&mut self, kind: &ConstraintKind) -> bool {
+        // A number may still turn out to be an integer or a float.
+        let decided = |ty: Option<Type>| ty.is_some_and(|ty| ty != Type::Number);
+        match *kind {
+            ConstraintKind::Same(..) => true,
+            ConstraintKind::Compare { left, right, .. } => match (self.type_of(left), self.type_of(right)) {
+                (Some(_), Some(_)) => true,
+                (Some(ty), None) | (None, Some(ty)) => !numeric(ty),
+                (None, None) => false,
+            },
+            ConstraintKind::Arithmetic { op, left, right, .. } => {
+                let (left, right) = (self.type_of(left), self.type_of(right));
+                (matches!(op, BinaryOperator::Add) && (left == Some(Type::String) || right == Some(Type::String)))
+                    || (decided(left) && decided(right))
+            }
+        }
+    }
+
+    fn apply(&mut self, kind: ConstraintKind, span: Span) -> Result<(), SemanticError> {
+        match kind {
+            ConstraintKind::Same(a, b, describe) => self.unify(a, b, span, describe).map(|_| ()),
+            ConstraintKind::Compare { op, left, right } => {
+                // Integers and floats compare with each other without either changing type.
+                let (l, r) = (self.type_of(left), self.type_of(right));
+                if l.is_some_and(numeric) && r.is_some_and(numeric) {
+                    return Ok(());
+                }
+                let symbol = symbol(op);
+                self.unify(left, right, span, |l, r| format!("Cannot compare {} with {} using '{}'", l, r, symbol))
+                    .map(|_| ())
+            }
+            ConstraintKind::Arithmetic { op, left, right, result } => self.arithmetic(op, left, right, result, span),
+        }
+    }
+
+    fn arithmetic(&mut self, op: BinaryOperator, left: usize, right: usize, result: usize, span: Span) -> Result<(), SemanticError> {
+        let symbol = symbol(op);
+        let (l, r) = (self.type_of(left), self.type_of(right));
+        let describe_result = |found: Type, expected: Type| format!(
+            "Operator '{}' gives {} but {} is expected here", symbol, found, expected
+        );
+
+        if matches!(op, BinaryOperator::Add) && (l == Some(Type::String) || r == Some(Type::String) || (l.is_none() && r.is_none())) {
+            // Either two strings or two numbers, and the sum is of the same
+            // type; whatever fixes the type of one fixes that of the others.
+            let sum = self.unify(left, right, span, |l, r| format!("Cannot add {} to {}", r, l))?;
+            return self.unify(sum, result, span, describe_result).map(|_| ());
+        }
+
+        for class in [left, right] {
+            let expected = self.fresh(Some(Type::Number), None);
+            self.unify(class, expected, span, |found, _| format!(
+                "Operator '{}' expects numbers but found {}", symbol, found
+            ))?;
+        }
+        let ty = match (self.type_of(left), self.type_of(right)) {
+            (Some(Type::Integer), Some(Type::Integer)) => Type::Integer,
+            (Some(Type::Float), _) | (_, Some(Type::Float)) => Type::Float,
+            _ => Type::Number,
+        };
+        let computed = self.fresh(Some(ty), Some(span));
+        self.unify(computed, result, span, describe_result).map(|_| ())
+    }
+
+    /// Merges the classes of `a` and `b`, reporting a conflict at `span` with
+    /// the message `describe` builds from their types.
+    fn unify(
+        &mut self,
+        a: usize,
+        b: usize,
+        span: Span,
+        describe: impl FnOnce(Type, Type) -> String,
+    ) -> Result<usize, SemanticError> {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return Ok(a);
+        }
+
+        let ty = match (self.classes[a].ty, self.classes[b].ty) {
+            (Some(ta), Some(tb)) => match ta.meet(tb) {
+                Some(ty) if (self.classes[a].declared && ty != ta) || (self.classes[b].declared && ty != tb) => None,
+                ty => ty,
+            }
+            .ok_or_else(|| self.conflict(span, describe(ta, tb), [a, b]))
+            .map(Some)?,
+            (ta, tb) => ta.or(tb),
+        };
+
+        // Keep a declared class as the root, so that it stays reported as declared.
+        let (a, b) = if self.classes[a].declared { (b, a) } else { (a, b) };
+
+        let origin = self.classes[b].origin.or(self.classes[a].origin).or(ty.map(|_| span));
+        self.classes[a].parent = b;
+        self.classes[b].ty = ty;
+        self.classes[b].origin = origin;
+        self.classes[b].declared |= self.classes[a].declared;
+        Ok(b)
+    }
+
+    fn conflict(&self, span: Span, message: String, classes: [usize; 2]) -> SemanticError {
+        let mut error = SemanticError::new(Code::TypeMismatch, self.source, span, message);
+        for class in classes {
+            let class = &self.classes[class];
+            if let (Some(ty), Some(origin)) = (class.ty, class.origin) {
+                if origin != span {
+                    let how = if class.declared { "declared" } else { "inferred" };
+                    error = error.with_note(self.source, origin, format!("Type {} is {} here", ty, how));
+                }
+            }
+        }
+        error
+    }
+
+    fn rule(&mut self, rule: &'a Rule) {
+        let mut variables = HashMap::new();
+        self.body(&rule.body, &mut variables);
+        self.atom(&rule.head, &mut variables);
+    }
+
+    /// Collects the constraints of the literals of a rule body or query.
+    fn body(&mut self, body: &'a [Literal], variables: &mut HashMap<&'a Identifier, usize>) {
+        for literal in body {
+            match literal {
+                Literal::Positive(atom) | Literal::Negative(atom) => self.atom(atom, variables),
+                Literal::Condition(condition) => {
+                    let class = self.expr(condition, variables);
+                    self.require(class, Type::Boolean, condition.span(), |found| format!(
+                        "Condition must be boolean but is {}", found
+                    ));
+                }
+            }
+        }
+    }
+
+    fn atom(&mut self, atom: &'a Atom, variables: &mut HashMap<&'a Identifier, usize>) {
+        let columns = match self.columns.get(&atom.name) {
+            Some(columns) => columns.clone(),
+            None => {
+                let columns: Vec<usize> = atom.terms.iter().map(|_| self.fresh(None, None)).collect();
+                self.columns.insert(atom.name.clone(), columns.clone());
+                columns
+            }
+        };
+
+        for (index, (term, column)) in atom.terms.iter().zip(columns).enumerate() {
+            let class = self.expr(term, variables);
+            self.same(class, column, term.span(), move |term, column| format!(
+                "Column {} of '{}' has type {} but {} is used here", index + 1, atom.name, column, term
+            ));
+        }
+    }
+
+    /// The class of `expr`, after collecting the constraints of its subexpressions.
+    fn expr(&mut self, expr: &'a Expression, variables: &mut HashMap<&'a Identifier, usize>) -> usize {
+        let span = expr.span();
+        match expr {
+            Expression::Constant(constant, _) => self.fresh(Some(Type::from(constant)), Some(span)),
+            Expression::Variable(name) => match variables.get(name) {
+                Some(class) => *class,
+                None => {
+                    let class = self.fresh(None, None);
+                    variables.insert(name, class);
+                    class
+                }
+            },
+            Expression::Wildcard(_) => self.fresh(None, None),
+            Expression::Paren(expr, _) => self.expr(expr, variables),
+            Expression::Unary { expr, .. } => {
+                let class = self.expr(expr, variables);
+                self.require(class, Type::Number, span, |found| format!(
+                    "Operator '-' expects a number but found {}", found
+                ));
+                class
+            }
+            Expression::Aggregate(aggregate) => {
+                let arg = aggregate.arg.as_deref().map(|arg| self.expr(arg, variables));
+                let func = aggregate.func;
+                let numeric = |class: usize, this: &mut Self| this.require(class, Type::Number, span, move |found| format!(
+                    "Aggregate '{}' expects numbers but found {}", func, found
+                ));
+
+                match (func, arg) {
+                    (AggregateFunction::Count, _) | (_, None) => self.fresh(Some(Type::Integer), Some(span)),
+                    (AggregateFunction::Sum, Some(arg)) => {
+                        numeric(arg, self);
+                        arg
+                    }
+                    (AggregateFunction::Avg, Some(arg)) => {
+                        numeric(arg, self);
+                        self.fresh(Some(Type::Float), Some(span))
+                    }
+                    (AggregateFunction::Min | AggregateFunction::Max, Some(arg)) => arg,
+                }
+            }
+            Expression::Binary { left, op, right } => {
+                let left = self.expr(left, variables);
+                let right = self.expr(right, variables);
+                self.binary(*op, left, right, span)
+            }
+        }
+    }
+
+    fn binary(&mut self, op: BinaryOperator, left: usize, right: usize, span: Span) -> usize {
+        match op {
+            BinaryOperator::Eq
+            | BinaryOperator::NotEq
+            | BinaryOperator::Lt
+            | BinaryOperator::LtEq
+            | BinaryOperator::Gt
+            | BinaryOperator::GtEq => {
+                self.push(span, ConstraintKind::Compare { op, left, right });
+                self.fresh(Some(Type::Boolean), Some(span))
+            }
+            _ => {
+                let result = self.fresh(None, None);
+                self.push(span, ConstraintKind::Arithmetic { op, left, right, result });
+                result
+            }
+        }
+    }
+}
+
+fn numeric(ty: Type) -> bool {
+    matches!(ty, Type::Integer | Type::Float | Type::Number)
+}
+
+fn symbol(op: BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Sub => "-",
+        BinaryOperator::Mul => "*",
+        BinaryOperator::Div => "/",
+        BinaryOperator::Mod => "%",
+        BinaryOperator::Eq => "==",
+        BinaryOperator::NotEq => "!=",
+        BinaryOperator::Lt => "<",
+        BinaryOperator::LtEq => "<=",
+        BinaryOperator::Gt => ">",
+        BinaryOperator::GtEq => ">=",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Parser, lexer::Lexer};
+
+    /// The line, column and message of an error.
+    type Error = (usize, usize, String);
+
+    /// The inferred type of each column of `relation`, or the errors found.
+    fn infer(source: &str, relation: &str) -> Result<Vec<Option<Type>>, Vec<Error>> {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty());
+        let (program, errors) = Program::parse_all(&mut Parser::new(source, tokens));
+        assert!(errors.is_empty());
+        let mut symbols = SymbolTable::new(&program, source).unwrap();
+
+        match infer_types(&program, &mut symbols, source) {
+            Ok(()) => Ok(symbols.relations.iter()
+                .find(|(name, _)| name.name == relation)
+                .map(|(_, schema)| schema.types.clone())
+                .unwrap()),
+            Err(errors) => Err(errors.iter()
+                .map(|error| {
+                    let diagnostic = error.diagnostic();
+                    let span = diagnostic.span().unwrap();
+                    (span.start_line, span.start_column, diagnostic.message)
+                })
+                .collect()),
+        }
+    }
+
+    /// Infers the types of `relation` with the rules of `source` in their
+    /// given order and in reverse, checking that both agree.
+    fn infer_in_any_order(facts: &str, rules: &[&str], relation: &str) -> Vec<Option<Type>> {
+        let forward = format!("{}\n{}\n", facts, rules.join("\n"));
+        let backward = format!("{}\n{}\n", facts, rules.iter().rev().copied().collect::<Vec<_>>().join("\n"));
+        let types = infer(&forward, relation).unwrap();
+        assert_eq!(infer(&backward, relation).unwrap(), types);
+        types
+    }
+
+    #[test]
+    fn arithmetic_results_do_not_depend_on_the_order_of_rules() {
+        let types = infer_in_any_order("U(1.5).", &["S(x + 1) :- T(x).", "T(y) :- U(y)."], "S");
+        assert_eq!(types, vec![Some(Type::Float)]);
+    }
+
+    #[test]
+    fn comparisons_do_not_depend_on_the_order_of_rules() {
+        let types = infer_in_any_order(
+            "E(1). F(2.5).",
+            &["Q(x) :- G(x, y), x < y.", "G(a, b) :- E(a), F(b)."],
+            "G",
+        );
+        assert_eq!(types, vec![Some(Type::Integer), Some(Type::Float)]);
+    }
+
+    #[test]
+    fn comparisons_of_unknown_types_share_a_type() {
+        let types = infer_in_any_order("E(\"a\").", &["P(x, y) :- Q(x, y), x == y.", "Q(x, y) :- E(x), E(y)."], "P");
+        assert_eq!(types, vec![Some(Type::String), Some(Type::String)]);
+    }
+
+    #[test]
+    fn sums_of_unknown_types_share_a_type() {
+        let types = infer_in_any_order("E(\"a\").", &["P(x + y) :- Q(x, y).", "Q(x, y) :- E(x), E(y)."], "P");
+        assert_eq!(types, vec![Some(Type::String)]);
+    }
+
+    #[test]
+    fn reports_mismatched_types() {
+        let errors = infer(
+            ".decl D(a: int).\nE(\"a\", 1).\n\
+             P(x) :- E(x, y), x < y.\n\
+             Q(x + 1) :- E(x, _).\n\
+             R(y) :- E(_, y), y + 1.\n\
+             D(x) :- E(x, _).\n",
+            "P",
+        ).unwrap_err();
+        assert_eq!(errors, vec![
+            (3, 18, "Cannot compare string with integer using '<'".to_string()),
+            (4, 3, "Cannot add integer to string".to_string()),
+            (5, 18, "Operator '+' gives integer but boolean is expected here".to_string()),
+            (6, 3, "Column 1 of 'D' has type integer but string is used here".to_string()),
+        ]);
+    }
+}